@@ -0,0 +1,463 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+
+use crate::spectrum::Spectrum;
+
+// Oversampling and sinc taps on each side used for true peak estimation.
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+const TRUE_PEAK_TAPS: usize = 12;
+// Frames oversampled at a time.
+const TRUE_PEAK_BLOCK: usize = 4096;
+// Largest delay searched for between two channels.
+const MAX_DELAY_SECS: f32 = 0.05;
+// Weakest normalized correlation that still counts as a shared signal.
+const MIN_DELAY_CORRELATION: f32 = 0.3;
+
+// How interleaved channels are collapsed into the single analyzed signal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelMode {
+    Sum,
+    Mid,
+    Side,
+    Left,
+    Right,
+    // The louder channel in every frequency bin, so sounds panned to one side
+    // show at full level and nothing cancels. It only applies to spectra;
+    // time-domain analysis uses the mid signal instead.
+    Max,
+}
+
+impl ChannelMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sum" => Some(ChannelMode::Sum),
+            "mid" => Some(ChannelMode::Mid),
+            "side" => Some(ChannelMode::Side),
+            "left" => Some(ChannelMode::Left),
+            "right" => Some(ChannelMode::Right),
+            "max" => Some(ChannelMode::Max),
+            _ => None,
+        }
+    }
+
+    // Collapses one interleaved frame. Mono input has no side signal, and
+    // both single-channel modes read its only channel.
+    pub fn collapse(&self, frame: &[f32]) -> f32 {
+        let left = frame[0];
+        let right = frame.get(1).copied().unwrap_or(left);
+        match self {
+            ChannelMode::Sum => frame.iter().sum(),
+            ChannelMode::Mid | ChannelMode::Max => frame.iter().sum::<f32>() / frame.len() as f32,
+            ChannelMode::Side => (left - right) / 2.0,
+            ChannelMode::Left => left,
+            ChannelMode::Right => right,
+        }
+    }
+}
+
+// Mixes interleaved samples down to a single channel.
+pub fn downmix(samples: &[f32], channels: usize, mode: ChannelMode) -> Vec<f32> {
+    samples
+        .chunks_exact(channels)
+        .map(|frame| mode.collapse(frame))
+        .collect()
+}
+
+// The samples of one channel of interleaved samples, read in place.
+pub fn channel(
+    samples: &[f32],
+    channels: usize,
+    channel: usize,
+) -> std::iter::StepBy<std::iter::Skip<std::slice::Iter<'_, f32>>> {
+    samples.iter().skip(channel).step_by(channels)
+}
+
+// Stereo features read the first two channels, L and R. Mono material has
+// no second one, so every feature checks here first and has a defined mono
+// result instead: the correlation is 1, there is no width or delay, and the
+// side channel mode and mid/side stages have nothing to work on.
+pub fn is_mono(channels: usize) -> bool {
+    channels < 2
+}
+
+// Shown in place of a stereo measurement that mono material doesn't have.
+pub const MONO_NOTE: &str = "n/a, mono";
+
+// Phase correlation of L and R from -1, opposite polarity, through 0,
+// unrelated or silent, to 1, identical. Mono is identical to itself.
+pub fn correlation(samples: &[f32], channels: usize) -> f32 {
+    if is_mono(channels) {
+        return 1.0;
+    }
+    let (mut product, mut left_power, mut right_power) = (0.0, 0.0, 0.0);
+    for frame in samples.chunks_exact(channels) {
+        product += frame[0] * frame[1];
+        left_power += frame[0] * frame[0];
+        right_power += frame[1] * frame[1];
+    }
+    let norm = (left_power * right_power).sqrt();
+    if norm > 0.0 { product / norm } else { 0.0 }
+}
+
+// Level of the side signal relative to the mid in dB: very low for nearly
+// centred material, 0 where they are equal and positive when the channels
+// are mostly out of phase. `None` for mono, which has no width.
+pub fn width_db(samples: &[f32], channels: usize) -> Option<f32> {
+    if is_mono(channels) {
+        return None;
+    }
+    let (mut mid_power, mut side_power) = (0.0, 0.0);
+    for frame in samples.chunks_exact(channels) {
+        mid_power += (frame[0] + frame[1]).powi(2);
+        side_power += (frame[0] - frame[1]).powi(2);
+    }
+    Some(to_db(side_power.sqrt()) - to_db(mid_power.sqrt()))
+}
+
+// Short name of a channel for display.
+pub fn channel_name(channel: usize, channels: usize) -> String {
+    match (channels, channel) {
+        (1, _) => "mono".to_string(),
+        (2, 0) => "L".to_string(),
+        (2, 1) => "R".to_string(),
+        _ => format!("ch{}", channel + 1),
+    }
+}
+
+// Frames of `size` samples starting every `hop` samples.
+pub fn windows(samples: &[f32], size: usize, hop: usize) -> impl Iterator<Item = &[f32]> {
+    (0..)
+        .map(move |i| i * hop)
+        .take_while(move |start| start + size <= samples.len())
+        .map(move |start| &samples[start..start + size])
+}
+
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+pub fn to_db(amplitude: f32) -> f32 {
+    20.0 * (amplitude + 1e-10).log10()
+}
+
+// Largest absolute sample value, of a buffer or of one `channel`.
+pub fn peak<'a>(samples: impl IntoIterator<Item = &'a f32>) -> f32 {
+    samples.into_iter().fold(0.0, |max, s| max.max(s.abs()))
+}
+
+// Peak of the reconstructed signal between samples, which can exceed the
+// sample peak by a few dB and clip after conversion. Estimated by 4x
+// oversampling with a Hann windowed sinc, as in ITU-R BS.1770. Reads one
+// channel of interleaved samples.
+pub fn true_peak(samples: &[f32], channels: usize, channel: usize) -> f32 {
+    let mut max = 0.0f32;
+    TruePeak::new().for_each(samples, channels, channel, |_, peak| max = max.max(peak));
+    max
+}
+
+// The true peak oversampler. A channel is filtered a block at a time through
+// a scratch buffer holding the block and the taps around it, which is kept
+// for the next call.
+pub struct TruePeak {
+    kernels: Vec<Vec<f32>>,
+    scratch: Vec<f32>,
+}
+
+impl TruePeak {
+    pub fn new() -> Self {
+        let taps = TRUE_PEAK_TAPS as isize;
+        let kernels = (1..TRUE_PEAK_OVERSAMPLING)
+            .map(|phase| {
+                let offset = phase as f32 / TRUE_PEAK_OVERSAMPLING as f32;
+                (1 - taps..=taps)
+                    .map(|k| {
+                        let x = offset - k as f32;
+                        let window = 0.5 * (1.0 + (std::f32::consts::PI * x / taps as f32).cos());
+                        let sinc = (std::f32::consts::PI * x).sin() / (std::f32::consts::PI * x);
+                        sinc * window
+                    })
+                    .collect()
+            })
+            .collect();
+        TruePeak {
+            kernels,
+            scratch: Vec::with_capacity(TRUE_PEAK_BLOCK + 2 * TRUE_PEAK_TAPS),
+        }
+    }
+
+    // Calls `f` with every frame of one channel of interleaved samples and
+    // the peak of the reconstructed signal from it up to the next frame.
+    pub fn for_each(
+        &mut self,
+        samples: &[f32],
+        channels: usize,
+        channel: usize,
+        mut f: impl FnMut(usize, f32),
+    ) {
+        let taps = TRUE_PEAK_TAPS;
+        let frames = samples.len() / channels;
+        for start in (0..frames).step_by(TRUE_PEAK_BLOCK) {
+            let len = TRUE_PEAK_BLOCK.min(frames - start);
+            // Frames `start + 1 - taps` to `start + len + taps`, zero
+            // outside the signal.
+            let first = (start + 1).saturating_sub(taps);
+            let last = (start + len + taps).min(frames);
+            self.scratch.clear();
+            self.scratch.resize(first + taps - 1 - start, 0.0);
+            self.scratch.extend(self::channel(
+                &samples[first * channels..last * channels],
+                channels,
+                channel,
+            ));
+            self.scratch.resize(len + 2 * taps, 0.0);
+
+            for n in 0..len {
+                let context = &self.scratch[n..n + 2 * taps];
+                let mut max = context[taps - 1].abs();
+                for kernel in &self.kernels {
+                    let value: f32 = context.iter().zip(kernel).map(|(s, c)| s * c).sum();
+                    max = max.max(value.abs());
+                }
+                f(start + n, max);
+            }
+        }
+    }
+}
+
+// Number of separate overloads in a channel, counting every run of
+// consecutive samples at or above `level` once.
+pub fn clip_count<'a>(samples: impl IntoIterator<Item = &'a f32>, level: f32) -> usize {
+    let mut count = 0;
+    let mut clipping = false;
+    for s in samples {
+        let clipped = s.abs() >= level;
+        if clipped && !clipping {
+            count += 1;
+        }
+        clipping = clipped;
+    }
+    count
+}
+
+// Delay of R behind L of interleaved samples, in samples and milliseconds,
+// at the strongest peak of their cross-correlation. Negative when R leads.
+// The peak is taken by magnitude, so a pair with inverted polarity is still
+// aligned. The correlation is summed over FFTs of blocks much longer than
+// the largest searched delay, so long files don't need one huge transform.
+// `None` if the channels are silent or too unrelated to align.
+pub fn channel_delay_samples(
+    samples: &[f32],
+    channels: usize,
+    sample_rate: u32,
+) -> Option<(isize, f32)> {
+    let len = samples.len() / channels;
+    let max_lag = ((MAX_DELAY_SECS * sample_rate as f32) as usize).min(len / 4);
+    let energy = |c: usize| channel(samples, channels, c).map(|s| s * s).sum::<f32>();
+    let scale = (energy(0) * energy(1)).sqrt();
+    if max_lag == 0 || scale == 0.0 {
+        return None;
+    }
+    let block = (8 * max_lag).next_power_of_two();
+    // Padding every block to twice its length keeps the correlation linear.
+    let size = 2 * block;
+    let mut planner = FftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(size);
+    let inverse = planner.plan_fft_inverse(size);
+
+    let transform = |block: &[f32], c: usize| {
+        let mut buffer: Vec<Complex<f32>> = channel(block, channels, c)
+            .map(|&s| Complex::new(s, 0.0))
+            .collect();
+        buffer.resize(size, Complex::new(0.0, 0.0));
+        forward.process(&mut buffer);
+        buffer
+    };
+    let mut cross = vec![Complex::new(0.0, 0.0); size];
+    for start in (0..len).step_by(block) {
+        let end = (start + block).min(len);
+        let block = &samples[start * channels..end * channels];
+        let (l, r) = (transform(block, 0), transform(block, 1));
+        for ((sum, l), r) in cross.iter_mut().zip(&l).zip(&r) {
+            *sum += l.conj() * r;
+        }
+    }
+    inverse.process(&mut cross);
+
+    // The correlation at a negative lag wraps around to the end.
+    let correlation = |lag: isize| cross[lag.rem_euclid(size as isize) as usize].re.abs();
+    let lag = (-(max_lag as isize)..=max_lag as isize)
+        .max_by(|&a, &b| correlation(a).total_cmp(&correlation(b)))?;
+    // The inverse transform is unnormalized, so scale by its size too.
+    if correlation(lag) / (size as f32 * scale) < MIN_DELAY_CORRELATION {
+        return None;
+    }
+    Some((lag, lag as f32 * 1000.0 / sample_rate as f32))
+}
+
+// Unsmoothed band levels of every hop of interleaved samples, one row per
+// frame. `hop_size` is in frames.
+pub fn band_matrix(spectrum: &Spectrum, samples: &[f32], hop_size: usize) -> Vec<Vec<f32>> {
+    let channels = spectrum.channels;
+    windows(samples, spectrum.fft_size * channels, hop_size * channels)
+        .map(|frame| spectrum.band_levels(frame))
+        .collect()
+}
+
+// Average level of every band over interleaved samples, in dB. Frames are
+// averaged as power so quiet passages don't drag loud bands down.
+pub fn average_band_levels(spectrum: &Spectrum, samples: &[f32]) -> Vec<f32> {
+    let mut power = vec![0.0; spectrum.bands];
+    let mut frames = 0;
+
+    let window = spectrum.fft_size * spectrum.channels;
+    for frame in windows(samples, window, window / 2) {
+        let levels = spectrum.band_levels(frame);
+        for (sum, db) in power.iter_mut().zip(levels) {
+            *sum += 10f32.powf(db / 10.0);
+        }
+        frames += 1;
+    }
+
+    power
+        .iter()
+        .map(|&sum| {
+            if frames == 0 {
+                spectrum.min_db
+            } else {
+                10.0 * (sum / frames as f32).log10()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::window::Window;
+
+    // Total harmonic distortion of a tone from its magnitude spectrum: the RMS
+    // sum of the 2nd up to the `harmonics`-th harmonic relative to the
+    // fundamental, as a fraction. Each is read as the strongest bin within one of
+    // its nominal bin, so a tone between bins is still caught. Harmonics beyond
+    // the spectrum are left out.
+    pub(crate) fn thd(magnitudes: &[f32], fundamental_bin: usize, harmonics: usize) -> f32 {
+        let near = |bin: usize| {
+            magnitudes[bin.saturating_sub(1)..(bin + 2).min(magnitudes.len())]
+                .iter()
+                .fold(0.0f32, |max, &m| max.max(m))
+        };
+        let fundamental = near(fundamental_bin);
+        if fundamental == 0.0 {
+            return 0.0;
+        }
+        let power: f32 = (2..=harmonics)
+            .map(|k| k * fundamental_bin)
+            .take_while(|&bin| bin < magnitudes.len())
+            .map(|bin| near(bin).powi(2))
+            .sum();
+        power.sqrt() / fundamental
+    }
+
+    // Hann windowed magnitude spectrum of a signal, up to Nyquist.
+    pub(crate) fn magnitudes(signal: &[f32]) -> Vec<f32> {
+        let mut buffer: Vec<Complex<f32>> = signal
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| Complex::new(s * Window::Hann.coefficient(i, signal.len()), 0.0))
+            .collect();
+        FftPlanner::new()
+            .plan_fft_forward(signal.len())
+            .process(&mut buffer);
+        buffer[..signal.len() / 2]
+            .iter()
+            .map(|c| c.norm())
+            .collect()
+    }
+
+    // Sum of sines at multiples of `bin` over `len` samples, one amplitude
+    // per harmonic starting with the fundamental.
+    pub(crate) fn harmonic_tone(len: usize, bin: usize, amplitudes: &[f32]) -> Vec<f32> {
+        (0..len)
+            .map(|n| {
+                amplitudes
+                    .iter()
+                    .enumerate()
+                    .map(|(k, a)| {
+                        let phase = 2.0 * std::f32::consts::PI * ((k + 1) * bin * n) as f32;
+                        a * (phase / len as f32).sin()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn thd_of_known_harmonics() {
+        let tone = harmonic_tone(4096, 40, &[1.0, 0.01, 0.005]);
+        let expected = (0.01f32.powi(2) + 0.005f32.powi(2)).sqrt();
+        let measured = thd(&magnitudes(&tone), 40, 5);
+        assert!(
+            (measured - expected).abs() < 1e-4,
+            "{} != {}",
+            measured,
+            expected
+        );
+    }
+
+    #[test]
+    fn thd_of_pure_sine_is_negligible() {
+        let tone = harmonic_tone(4096, 40, &[1.0]);
+        assert!(thd(&magnitudes(&tone), 40, 5) < 1e-4);
+    }
+
+    // Stereo at a quarter of the sample rate, L sampled 45 degrees off its
+    // crests so every sample reads 0.707 while the waveform reaches 1.
+    fn quarter_rate_stereo(frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|n| {
+                let phase = std::f32::consts::FRAC_PI_2 * n as f32;
+                [
+                    (phase + std::f32::consts::FRAC_PI_4).sin(),
+                    0.5 * phase.sin(),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn true_peak_finds_intersample_peaks_per_channel() {
+        let samples = quarter_rate_stereo(2 * TRUE_PEAK_BLOCK + 100);
+        assert!((peak(channel(&samples, 2, 0)) - 0.707).abs() < 0.001);
+        let left = true_peak(&samples, 2, 0);
+        let right = true_peak(&samples, 2, 1);
+        assert!(left > 0.98, "left true peak {}", left);
+        assert!((right - 0.5).abs() < 0.02, "right true peak {}", right);
+    }
+
+    #[test]
+    fn true_peak_blocks_match_filtering_in_one_pass() {
+        let samples = quarter_rate_stereo(2 * TRUE_PEAK_BLOCK + 100);
+        let left: Vec<f32> = channel(&samples, 2, 0).copied().collect();
+        let mut true_peak = TruePeak::new();
+        let mut frames = 0;
+        let kernels = true_peak.kernels.clone();
+        true_peak.for_each(&samples, 2, 0, |n, value| {
+            let taps = TRUE_PEAK_TAPS as isize;
+            let expected = kernels
+                .iter()
+                .map(|kernel| {
+                    (1 - taps..=taps)
+                        .zip(kernel)
+                        .filter_map(|(k, c)| left.get((n as isize + k) as usize).map(|s| s * c))
+                        .sum::<f32>()
+                        .abs()
+                })
+                .fold(left[n].abs(), f32::max);
+            assert!((value - expected).abs() < 1e-5, "frame {}", n);
+            frames += 1;
+        });
+        assert_eq!(frames, left.len());
+    }
+}
@@ -0,0 +1,59 @@
+use crate::gain::db_to_gain;
+
+// Time-varying gain defined by breakpoints. Gain is interpolated linearly
+// between points (in amplitude, so a `-inf` point fades to true silence) and
+// held before the first and after the last point.
+pub struct GainEnvelope {
+    // (seconds, linear gain), sorted by time.
+    points: Vec<(f32, f32)>,
+}
+
+impl GainEnvelope {
+    // Parses comma separated `ms:dB` pairs such as `0:-inf,1000:0`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut points = Vec::new();
+        for token in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (ms, db) = token
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid automation point '{}': expected ms:dB", token))?;
+            let ms: f32 = ms
+                .parse()
+                .map_err(|_| format!("Invalid automation time in '{}'", token))?;
+            let db: f32 = db
+                .parse()
+                .map_err(|_| format!("Invalid automation level in '{}'", token))?;
+            if let Some(&(last, _)) = points.last()
+                && ms / 1000.0 < last
+            {
+                return Err(format!("Automation point '{}' is out of order", token));
+            }
+            points.push((ms / 1000.0, db_to_gain(db)));
+        }
+        if points.is_empty() {
+            return Err("Automation needs at least one point".to_string());
+        }
+        Ok(GainEnvelope { points })
+    }
+
+    pub fn gain_at(&self, secs: f32) -> f32 {
+        let next = self.points.partition_point(|&(t, _)| t <= secs);
+        if next == 0 {
+            return self.points[0].1;
+        }
+        if next == self.points.len() {
+            return self.points[next - 1].1;
+        }
+        let (t0, g0) = self.points[next - 1];
+        let (t1, g1) = self.points[next];
+        g0 + (g1 - g0) * (secs - t0) / (t1 - t0)
+    }
+
+    pub fn apply(&self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        for (i, frame) in samples.chunks_mut(channels).enumerate() {
+            let gain = self.gain_at(i as f32 / sample_rate as f32);
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+}
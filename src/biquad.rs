@@ -0,0 +1,66 @@
+use crate::source::BlockProcessor;
+
+// Second-order IIR section with coefficients from the RBJ audio EQ cookbook.
+// Coefficients and state are kept in f64: narrow, low-frequency sections have
+// poles very close to the unit circle and lose accuracy in f32.
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    // Section from raw coefficients, normalized so that a0 is 1.
+    pub fn normalized(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn notch(freq: f32, q: f32, sample_rate: u32) -> Self {
+        let w0 = 2.0 * std::f64::consts::PI * freq as f64 / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * q as f64);
+        let cos = w0.cos();
+        Biquad::normalized(
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    // Gain of the section for a tone at `freq`.
+    pub fn magnitude_at(&self, freq: f32, sample_rate: u32) -> f64 {
+        let w = 2.0 * std::f64::consts::PI * freq as f64 / sample_rate as f64;
+        // |b0 + b1 z^-1 + b2 z^-2| / |1 + a1 z^-1 + a2 z^-2| at z = e^jw.
+        let response = |c0: f64, c1: f64, c2: f64| {
+            let re = c0 + c1 * w.cos() + c2 * (2.0 * w).cos();
+            let im = -c1 * w.sin() - c2 * (2.0 * w).sin();
+            (re * re + im * im).sqrt()
+        };
+        response(self.b0, self.b1, self.b2) / response(1.0, self.a1, self.a2)
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        // Transposed direct form II.
+        let x = input as f64;
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y as f32
+    }
+}
+
+impl BlockProcessor for Biquad {
+    fn process_sample(&mut self, input: f32) -> Option<f32> {
+        Some(self.process(input))
+    }
+}
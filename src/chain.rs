@@ -0,0 +1,299 @@
+use crate::analysis;
+use crate::dynamics::CompressorProcessor;
+use crate::filter::{HighPassFilterBlockProcessor, HumRemover, LowPassFilterBlockProcessor};
+use crate::fir::FirProcessor;
+use crate::gain::{GainProcessor, db_to_gain};
+use crate::gate::SpectralGate;
+use crate::saturator::{SaturationCurve, SaturatorProcessor};
+use crate::source::{BlockProcessor, FrameProcessor};
+use crate::stereo::{MidSideDecoder, MidSideEncoder, MonoToStereoProcessor};
+
+pub const DEFAULT_CHAIN: &str = "lp:500";
+
+pub const CHAIN_HELP: &str = "  lp:<hz>                      One-pole low-pass filter
+  hp:<hz>                      One-pole high-pass filter
+  gain:<db>                    Fixed gain
+  comp:<db>:<ratio>[:<attack ms>[:<release ms>]]
+                               Compressor (default attack 10 ms, release 100 ms)
+  sat:<soft|hard|tube>[:<drive>[:<mix>]]
+                               Saturator (default drive 2, mix 1)
+  dehum:<hz>[:<harmonics>[:<q>]]
+                               Notch out mains hum and harmonics (default 4 harmonics, Q 30)
+  sgate:<floor db|learn>[:<reduction db>[:<learn secs>]]
+                               Spectral gate: cut FFT bins below a floor, or below the
+                               noise learned from the first seconds (default 12 dB, 0.5 s)
+  widen[:<delay ms>[:<width>]] Mono to stereo widener (default 12 ms, width 0.5)
+  msenc                        Stereo to mid/side, so later stages see M and S
+  msdec                        Mid/side back to stereo";
+
+enum Stage {
+    // One processor per channel, so filter state is never shared between
+    // interleaved channels.
+    PerChannel(Vec<Box<dyn BlockProcessor + Send>>),
+    Frame(Box<dyn FrameProcessor>),
+}
+
+pub struct ProcessorChain {
+    stages: Vec<Stage>,
+    output_channels: usize,
+    scratch: Vec<f32>,
+    // Copy of every frame after this many stages, where the analyzer can
+    // listen in: 0 is the input and the stage count the output.
+    pub tap: usize,
+    tapped: Vec<f32>,
+    // Channel count after every number of stages, starting with the input.
+    channels_after: Vec<usize>,
+}
+
+impl ProcessorChain {
+    // Parses a comma separated list of `name:param:...` tokens, e.g.
+    // `hp:30,comp:-18:4,gain:2`. See `CHAIN_HELP` for the available names.
+    pub fn parse(spec: &str, channels: usize, sample_rate: u32) -> Result<Self, String> {
+        let mut stages = Vec::new();
+        let mut channels_after = vec![channels];
+        let mut channels = channels;
+        for token in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (stage, output_channels) = parse_stage(token, channels, sample_rate)
+                .map_err(|e| format!("Invalid chain token '{}': {}", token, e))?;
+            stages.push(stage);
+            channels = output_channels;
+            channels_after.push(channels);
+        }
+        Ok(ProcessorChain {
+            tap: stages.len(),
+            stages,
+            output_channels: channels,
+            scratch: Vec::new(),
+            tapped: Vec::new(),
+            channels_after,
+        })
+    }
+
+    // Appends a FIR filter with `taps` on every channel. The taps come from
+    // `--fir` rather than a token, so their path is never split like one. A
+    // tap at the output stays there.
+    pub fn push_fir(&mut self, taps: &[f32]) {
+        if self.tap == self.stages.len() {
+            self.tap += 1;
+        }
+        let processors = (0..self.output_channels)
+            .map(|_| Box::new(FirProcessor::new(taps.to_vec())) as Box<dyn BlockProcessor + Send>)
+            .collect();
+        self.stages.push(Stage::PerChannel(processors));
+        self.channels_after.push(self.output_channels);
+    }
+
+    pub fn output_channels(&self) -> usize {
+        self.output_channels
+    }
+
+    pub fn stage_count(&self) -> usize {
+        self.stages.len()
+    }
+
+    // The last frame processed, at the tap.
+    pub fn tapped(&self) -> &[f32] {
+        &self.tapped
+    }
+
+    // Channel count of the signal at the tap.
+    pub fn tap_channels(&self) -> usize {
+        self.channels_after[self.tap]
+    }
+
+    // Runs interleaved input through the chain and returns the signal at the
+    // tap instead of the output.
+    pub fn render_tap(&mut self, samples: &[f32], channels: usize) -> Vec<f32> {
+        let mut output = Vec::new();
+        let mut tapped = Vec::with_capacity(samples.len() / channels * self.tap_channels());
+        for frame in samples.chunks_exact(channels) {
+            self.process_frame(frame, &mut output);
+            tapped.extend_from_slice(&self.tapped);
+        }
+        tapped
+    }
+
+    // Runs one interleaved input frame through every stage.
+    pub fn process_frame(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        output.clear();
+        output.extend_from_slice(input);
+        if self.tap == 0 {
+            self.tapped.clone_from(output);
+        }
+        for (index, stage) in self.stages.iter_mut().enumerate() {
+            match stage {
+                Stage::PerChannel(processors) => {
+                    for (sample, processor) in output.iter_mut().zip(processors.iter_mut()) {
+                        if let Some(processed_sample) = processor.process_sample(*sample) {
+                            *sample = processed_sample;
+                        }
+                    }
+                }
+                Stage::Frame(processor) => {
+                    self.scratch.clear();
+                    processor.process_frame(output, &mut self.scratch);
+                    std::mem::swap(output, &mut self.scratch);
+                }
+            }
+            if index + 1 == self.tap {
+                self.tapped.clone_from(output);
+            }
+        }
+    }
+}
+
+struct Params<'a>(Vec<&'a str>);
+
+impl Params<'_> {
+    fn number(&self, index: usize, default: Option<f32>) -> Result<f32, String> {
+        match self.0.get(index) {
+            Some(p) => p
+                .parse()
+                .map_err(|_| format!("parameter {} is not a number: {}", index + 1, p)),
+            None => default.ok_or_else(|| format!("missing parameter {}", index + 1)),
+        }
+    }
+
+    fn expect_at_most(&self, count: usize) -> Result<(), String> {
+        if self.0.len() > count {
+            Err(format!("expected at most {} parameters", count))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Builds the stage for a token and returns it with its output channel count.
+fn parse_stage(token: &str, channels: usize, sample_rate: u32) -> Result<(Stage, usize), String> {
+    let mut parts = token.split(':');
+    let name = parts.next().unwrap_or_default();
+    let params = Params(parts.collect());
+
+    match name {
+        "widen" => {
+            params.expect_at_most(2)?;
+            if channels != 1 {
+                return Err(format!("needs mono input, got {} channels", channels));
+            }
+            let processor = MonoToStereoProcessor::new(
+                params.number(0, Some(12.0))?,
+                params.number(1, Some(0.5))?.clamp(0.0, 1.0),
+                sample_rate,
+            );
+            Ok((Stage::Frame(Box::new(processor)), 2))
+        }
+        "msenc" | "msdec" => {
+            params.expect_at_most(0)?;
+            if analysis::is_mono(channels) {
+                return Err("needs stereo input, mono has no side signal".to_string());
+            }
+            if channels != 2 {
+                return Err(format!("needs stereo input, got {} channels", channels));
+            }
+            let processor: Box<dyn FrameProcessor> = if name == "msenc" {
+                Box::new(MidSideEncoder)
+            } else {
+                Box::new(MidSideDecoder)
+            };
+            Ok((Stage::Frame(processor), 2))
+        }
+        _ => {
+            let processors = (0..channels)
+                .map(|_| parse_processor(name, &params, sample_rate))
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok((Stage::PerChannel(processors), channels))
+        }
+    }
+}
+
+fn parse_processor(
+    name: &str,
+    params: &Params,
+    sample_rate: u32,
+) -> Result<Box<dyn BlockProcessor + Send>, String> {
+    match name {
+        "lp" => {
+            params.expect_at_most(1)?;
+            Ok(Box::new(LowPassFilterBlockProcessor {
+                prev: 0.0,
+                cutoff: params.number(0, None)?,
+                sample_rate,
+            }))
+        }
+        "hp" => {
+            params.expect_at_most(1)?;
+            Ok(Box::new(HighPassFilterBlockProcessor {
+                prev_input: 0.0,
+                prev_output: 0.0,
+                cutoff: params.number(0, None)?,
+                sample_rate,
+            }))
+        }
+        "dehum" => {
+            params.expect_at_most(3)?;
+            let harmonics = params.number(1, Some(4.0))?;
+            if harmonics < 1.0 {
+                return Err("at least one harmonic is required".to_string());
+            }
+            Ok(Box::new(HumRemover::new(
+                params.number(0, None)?,
+                harmonics as usize,
+                params.number(2, Some(30.0))?,
+                sample_rate,
+            )))
+        }
+        "gain" => {
+            params.expect_at_most(1)?;
+            Ok(Box::new(GainProcessor {
+                gain: db_to_gain(params.number(0, None)?),
+            }))
+        }
+        "comp" => {
+            params.expect_at_most(4)?;
+            let ratio = params.number(1, None)?;
+            if ratio < 1.0 {
+                return Err("ratio must be at least 1".to_string());
+            }
+            Ok(Box::new(CompressorProcessor::new(
+                params.number(0, None)?,
+                ratio,
+                params.number(2, Some(10.0))?,
+                params.number(3, Some(100.0))?,
+                sample_rate,
+            )))
+        }
+        "sat" => {
+            params.expect_at_most(3)?;
+            let curve_name = params.0.first().copied().unwrap_or_default();
+            let curve = SaturationCurve::from_name(curve_name)
+                .ok_or_else(|| format!("unknown saturation curve: {}", curve_name))?;
+            Ok(Box::new(SaturatorProcessor {
+                curve,
+                drive: params.number(1, Some(2.0))?,
+                mix: params.number(2, Some(1.0))?.clamp(0.0, 1.0),
+            }))
+        }
+        "sgate" => {
+            params.expect_at_most(3)?;
+            let reduction = params.number(1, Some(12.0))?;
+            if params.0.first() == Some(&"learn") {
+                let secs = params.number(2, Some(0.5))?;
+                if secs <= 0.0 {
+                    return Err("learn time must be positive".to_string());
+                }
+                Ok(Box::new(SpectralGate::learning(
+                    reduction,
+                    secs,
+                    sample_rate,
+                )))
+            } else {
+                params.expect_at_most(2)?;
+                Ok(Box::new(SpectralGate::with_floor(
+                    reduction,
+                    params.number(0, None)?,
+                )))
+            }
+        }
+        _ => Err(format!("unknown processor: {}", name)),
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::Write;
+
+use crate::analysis;
+use crate::pitch;
+use crate::spectrum::Spectrum;
+
+// Frequencies folded into pitch classes. Below, bins are too wide for single
+// semitones at usual FFT sizes; above, overtones blur the classes.
+const MIN_FREQ: f32 = 55.0;
+const MAX_FREQ: f32 = 5000.0;
+
+// Energy of every pitch class, C to B, in each hop of interleaved samples.
+// Every bin's power goes to the class of its nearest note relative to `a4`.
+// Each vector is scaled so its strongest class is 1; silent frames are all 0.
+pub fn chromagram(
+    spectrum: &Spectrum,
+    samples: &[f32],
+    hop_size: usize,
+    a4: f32,
+) -> Vec<[f32; 12]> {
+    let channels = spectrum.channels;
+    let bin_width = spectrum.sample_rate as f32 / spectrum.fft_len() as f32;
+    let first_bin = ((MIN_FREQ / bin_width).ceil() as usize).max(1);
+    let last_bin = ((MAX_FREQ / bin_width).floor() as usize).min(spectrum.fft_len() / 2 - 1);
+    let classes: Vec<usize> = (first_bin..=last_bin)
+        .map(|bin| {
+            let (note, _) = pitch::frequency_to_note(bin as f32 * bin_width, a4);
+            note.rem_euclid(12) as usize
+        })
+        .collect();
+
+    analysis::windows(samples, spectrum.fft_size * channels, hop_size * channels)
+        .map(|frame| {
+            let mut chroma = [0.0; 12];
+            let silent = analysis::to_db(analysis::rms(frame)) < spectrum.silence_db;
+            if silent {
+                return chroma;
+            }
+            let magnitudes = spectrum.frame_magnitudes(frame);
+            for (&class, magnitude) in classes.iter().zip(&magnitudes[first_bin..=last_bin]) {
+                chroma[class] += magnitude * magnitude;
+            }
+            let strongest = chroma.iter().cloned().fold(0.0, f32::max);
+            if strongest > 0.0 {
+                for value in chroma.iter_mut() {
+                    *value /= strongest;
+                }
+            }
+            chroma
+        })
+        .collect()
+}
+
+// Writes one row per frame: the time of the window's centre in seconds,
+// then the 12 classes.
+pub fn write_csv(
+    path: &str,
+    chroma: &[[f32; 12]],
+    hop_secs: f32,
+    window_secs: f32,
+) -> Result<(), String> {
+    let mut csv = format!("time,{}\n", pitch::NOTE_NAMES.join(","));
+    for (frame, values) in chroma.iter().enumerate() {
+        write!(csv, "{:.4}", frame as f32 * hop_secs + window_secs / 2.0).unwrap();
+        for value in values {
+            write!(csv, ",{:.4}", value).unwrap();
+        }
+        csv.push('\n');
+    }
+    std::fs::write(path, csv).map_err(|e| format!("Failed to write chromagram: {}", e))
+}
@@ -0,0 +1,678 @@
+use std::env;
+
+use crate::analysis::ChannelMode;
+use crate::chain::{CHAIN_HELP, DEFAULT_CHAIN};
+use crate::expect::Expectation;
+use crate::loudness;
+use crate::pitch;
+use crate::scale::BarScale;
+use crate::theme::BUILTIN_THEMES;
+use crate::weighting::Weighting;
+use crate::window::{WINDOW_NAMES, Window};
+
+pub struct Options {
+    pub file_path: String,
+    // Analyze a recording device instead of a file, see `--input`.
+    pub mic: bool,
+    pub input_device: Option<String>,
+    pub export: Option<String>,
+    pub bits: u16,
+    pub dither: Option<bool>,
+    pub noise_shaping: bool,
+    // Integrated loudness to gain the export to, see `--normalize-lufs`.
+    pub normalize_lufs: Option<f32>,
+    pub max_freq: Option<f32>,
+    pub references: Vec<String>,
+    // Files played in sync with the main one, loudness matched, see `--compare`.
+    pub compare: Vec<String>,
+    pub target: Option<String>,
+    pub band_smoothing: bool,
+    pub zero_pad: usize,
+    pub chain: String,
+    // Taps file of a FIR filter after the chain, see `--fir`.
+    pub fir: Option<String>,
+    pub tap: Option<usize>,
+    pub midi: Option<String>,
+    pub loudness_graph: Option<String>,
+    pub spectrogram: Option<String>,
+    // Size in pixels of the spectrogram itself, without axes and colorbar.
+    pub spec_width: usize,
+    pub spec_height: usize,
+    pub spec_fft: usize,
+    // Fraction of every FFT window shared with the next.
+    pub spec_overlap: f32,
+    // Levels spanned by the colormap.
+    pub spec_range: (f32, f32),
+    pub split_on_silence: Option<String>,
+    pub split_threshold_db: f32,
+    pub split_gap_secs: f32,
+    pub skip_silence: bool,
+    pub cue: Option<String>,
+    pub split_tracks: Option<String>,
+    pub skip_threshold_db: f32,
+    pub skip_gap_secs: f32,
+    pub features: Option<String>,
+    pub chromagram: Option<String>,
+    pub channel_mode: ChannelMode,
+    pub silence_db: Option<f32>,
+    // Time constant of the RMS and loudness readouts.
+    pub meter_window_ms: f32,
+    // Shows a peak readout behind this weighting, live and in the report.
+    pub peak_weighting: Option<Weighting>,
+    pub input_gain_db: f32,
+    pub window: Window,
+    pub enbw_correction: bool,
+    pub include_dc: bool,
+    pub bar_scale: BarScale,
+    pub band_peaks: bool,
+    pub hold_secs: Option<f32>,
+    pub energy_share: bool,
+    pub octave_colors: bool,
+    pub curve: bool,
+    pub floor_shading: bool,
+    pub beat_average: bool,
+    pub show_delay: bool,
+    pub theme: Option<String>,
+    pub tuner: bool,
+    pub a4: f32,
+    pub tuner_smoothing: f32,
+    pub preroll: bool,
+    pub trim_lead: bool,
+    pub interpolate: bool,
+    pub fps: f32,
+    pub print_frames: bool,
+    pub automation: Option<String>,
+    pub log: Option<String>,
+    pub log_interval: f32,
+    pub report: bool,
+    pub expect: Option<Expectation>,
+    pub fast_scan: usize,
+    #[cfg(feature = "osc")]
+    pub osc_target: Option<String>,
+    #[cfg(feature = "osc")]
+    pub osc_bands_address: String,
+    #[cfg(feature = "osc")]
+    pub osc_beat_address: String,
+}
+
+impl Options {
+    // Whether anything asked for needs the whole track decoded before
+    // playback, which a URL then is instead of being streamed.
+    pub fn needs_whole_track(&self) -> bool {
+        self.export.is_some()
+            || self.split_on_silence.is_some()
+            || self.split_tracks.is_some()
+            || self.chromagram.is_some()
+            || self.spectrogram.is_some()
+            || self.features.is_some()
+            || self.loudness_graph.is_some()
+            || self.midi.is_some()
+            || self.cue.is_some()
+            || self.automation.is_some()
+            || self.tap.is_some()
+            || !self.compare.is_empty()
+            || self.beat_average
+            || self.skip_silence
+    }
+
+    // Dithering defaults to on for 16-bit output, where truncation is audible.
+    pub fn dither_enabled(&self) -> bool {
+        self.dither.unwrap_or(self.bits == 16)
+    }
+}
+
+#[cfg(feature = "osc")]
+const OSC_USAGE: &str = "\n\
+     \x20 --osc <host:port>   Send band levels and beats as OSC messages over UDP\n\
+     \x20 --osc-bands-address <addr>\n\
+     \x20                     OSC address for band levels (default: /sound-heat/bands)\n\
+     \x20 --osc-beat-address <addr>\n\
+     \x20                     OSC address for beat triggers (default: /sound-heat/beat)";
+#[cfg(not(feature = "osc"))]
+const OSC_USAGE: &str = "";
+
+pub fn usage(program: &str) -> String {
+    format!(
+        "Usage: {} <file_path> [options]\n\
+         \x20      {} --input mic [options]\n\
+         \n\
+         Input:\n\
+         \x20 --input <file|mic>  Analyze a file while playing it, or the live signal of an\n\
+         \x20                     input device; nothing is played or processed then\n\
+         \x20 <url>               With the http feature, an http:// or https:// URL can be\n\
+         \x20                     given instead of the file; it is decoded while it plays,\n\
+         \x20                     on the live display, unless an output mode or an option\n\
+         \x20                     such as --compare needs the whole track first\n\
+         \x20 --input-device <name>\n\
+         \x20                     Input device to record from (default: the system default)\n\
+         \n\
+         Output modes (write a file instead of playing):\n\
+         \x20 --export <path>     Write the processed audio to a WAV file (FLAC is not supported)\n\
+         \x20 --bits <16|24>      Bit depth of the exported WAV (default: 16)\n\
+         \x20 --dither            Apply TPDF dither when exporting (default for 16-bit)\n\
+         \x20 --no-dither         Disable dither when exporting\n\
+         \x20 --noise-shaping     Apply first-order noise shaping to the dither\n\
+         \x20 --normalize-lufs <lufs>\n\
+         \x20                     Gain the export to this integrated loudness, e.g. -14, with\n\
+         \x20                     a limiter holding the true peak under -1 dBTP\n\
+         \x20 --features <path>   Write the time x band dB matrix to a NumPy .npy file\n\
+         \x20 --chromagram <path> Write the energy of the 12 pitch classes of every frame to a\n\
+         \x20                     CSV file, scaled so the strongest is 1 (tuned to --a4)\n\
+         \x20 --midi <path>       Write the detected melody to a MIDI file\n\
+         \x20 --loudness-graph <path>\n\
+         \x20                     Plot short-term loudness (EBU R 128) over time to a PNG\n\
+         \x20 --spectrogram <path>\n\
+         \x20                     Plot level over time and log frequency to a PNG, with axes\n\
+         \x20                     and a colorbar; uses --window, --max-freq and --channel-mode\n\
+         \x20 --spec-width <px>   Width of the spectrogram, without axes (default: 1600)\n\
+         \x20 --spec-height <px>  Height of the spectrogram, without axes (default: 600)\n\
+         \x20 --spec-fft <size>   FFT size of the spectrogram, a power of two from 256 to\n\
+         \x20                     32768 (default: 4096)\n\
+         \x20 --spec-overlap <fraction>\n\
+         \x20                     Overlap of consecutive FFT windows, 0 to 0.95 (default: 0.75)\n\
+         \x20 --spec-range <min>:<max>\n\
+         \x20                     Levels in dB spanned by the colors (default: -100:0)\n\
+         \x20 --split-on-silence <prefix>\n\
+         \x20                     Write every segment between silent gaps to <prefix>_NN.wav\n\
+         \x20 --split-threshold <db>\n\
+         \x20                     RMS level below which audio is silent (default: -50)\n\
+         \x20 --split-gap <secs>  Shortest silent gap that splits (default: 2)\n\
+         \x20 --split-tracks <prefix>\n\
+         \x20                     Write every track of the cue sheet to <prefix>_NN.wav\n\
+         \x20 --report            Print the levels and average spectrum of the file, and the\n\
+         \x20                     loudness of every track with --cue\n\
+         \x20 --fast-scan <n>     Report from every nth decoded packet only (implies --report);\n\
+         \x20                     much faster, but levels are estimates and short peaks\n\
+         \x20                     may be missed\n\
+         \x20 --expect <rates>[/<channels>]\n\
+         \x20                     Only check the sample rate and channel count, e.g.\n\
+         \x20                     \"44100|48000/2\", and exit with status 1 on a mismatch\n\
+         \n\
+         Display:\n\
+         \x20 --cue <path>        Cue sheet splitting the file into tracks; [ and ] jump\n\
+         \x20                     to the previous and next track\n\
+         \x20 --max-freq <hz>     Highest displayed frequency (default: min(20000, Nyquist))\n\
+         \x20 --reference <path>  Overlay the average spectrum of a reference track (repeatable)\n\
+         \x20 --compare <path>    Play another version in sync, e.g. a second master, gained to\n\
+         \x20                     the loudness of the quietest; a switches (repeatable)\n\
+         \x20 --target <path>     Show how far the average spectrum deviates from a target\n\
+         \x20                     curve of \"<hz> <db>\" lines, in the theme's low color\n\
+         \x20                     within 3 dB and its high color beyond\n\
+         \x20 --channel-mode <mode>\n\
+         \x20                     Analyze sum, mid, side, left, right or max, the louder\n\
+         \x20                     channel in every bin (default: mid)\n\
+         \x20 --silence-db <db>   RMS level below which the display empties (default: -60)\n\
+         \x20 --meter-window <ms> Integration time of the RMS and loudness readouts, e.g. 400\n\
+         \x20                     for EBU momentary or 3000 for short-term (default: 3000)\n\
+         \x20 --peak-weighting <flat|a|k>\n\
+         \x20                     Show the peak level after A or K (BS.1770) weighting, or\n\
+         \x20                     none, live and in the report\n\
+         \x20 --input-gain <db>   Gain applied before analysis, not playback, so quiet files\n\
+         \x20                     fill the display; every shown level is shifted by it\n\
+         \x20 --window <name>     Analysis window: {} (default: hann);\n\
+         \x20                     used by the display, the report and every export\n\
+         \x20 --enbw              Correct levels for the window's noise bandwidth, to read\n\
+         \x20                     noise floors and hiss accurately; tones then read low\n\
+         \x20 --include-dc        Count the DC bin in the lowest band; it is left out so a DC\n\
+         \x20                     offset doesn't inflate the low end (the window still spreads\n\
+         \x20                     some into the next bins, which \"hp:20\" removes)\n\
+         \x20 --bar-scale <scale> linear, or compressed[:<knee db>[:<ratio>]] to give quiet\n\
+         \x20                     levels more of the bar by compressing those above the knee\n\
+         \x20                     (default: linear; compressed defaults to -40 dB, ratio 4)\n\
+         \x20 --preroll           Seed the bars from the first window instead of ramping up\n\
+         \x20 --no-trim-lead      Start at the very beginning instead of skipping the silence\n\
+         \x20                     (below -60 dBFS) before the content\n\
+         \x20 --interpolate       Move the bars smoothly between analyzed frames\n\
+         \x20 --fps <n>           Frames drawn per second with --interpolate (default: 60)\n\
+         \x20 --band-peaks        Show the strongest frequency inside every band\n\
+         \x20 --hold <secs>       Show the loudest level of every band, held for this long\n\
+         \x20                     before it falls back at 20 dB/s\n\
+         \x20 --energy-share      Show every band's share of the total energy in percent\n\
+         \x20 --octave-colors     Color the bars by octave instead of by level\n\
+         \x20 --curve             Draw the spectrum as a filled curve over frequency across\n\
+         \x20                     the terminal instead of a bar per band\n\
+         \x20 --floor-shading     Draw a faint baseline cell for silent bands; bands without\n\
+         \x20                     any FFT bin at the current resolution stay blank\n\
+         \x20 --beat-average      Estimate the tempo and show the spectrum averaged over each\n\
+         \x20                     beat, updated once per beat, to see a groove's steady tone\n\
+         \x20 --show-delay        Show how far the right channel lags the left, e.g. to\n\
+         \x20                     check the alignment of a stereo microphone pair\n\
+         \x20 --band-smoothing    Smooth high bands more than low bands\n\
+         \x20 --zero-pad <factor> Zero-pad every window to factor times the FFT size; this\n\
+         \x20                     interpolates finer bins but adds no real resolution\n\
+         \x20 --theme <name|path> Color theme: {} or a file of key = \"color\" lines\n\
+         \x20                     for background, low, mid, high, peak and text\n\
+         \x20 --tuner             Show the nearest note and a cents needle instead of bars\n\
+         \x20 --a4 <hz>           Reference pitch for the tuner and MIDI export (default: 440)\n\
+         \x20 --tuner-smoothing <factor>\n\
+         \x20                     Needle smoothing from 0 to 0.99 (default: 0.8)\n\
+         \x20 --print-frames      Print one line per analyzed frame instead of drawing\n\
+         \x20 --log <path>        Append band levels, RMS, peak frequency and clipping to a\n\
+         \x20                     JSON Lines file during playback\n\
+         \x20 --log-interval <secs>\n\
+         \x20                     Seconds of audio summarized by every log entry (default: 1){}\n\
+         \n\
+         Processing:\n\
+         \x20 --chain <spec>      Processing chain, e.g. \"hp:30,comp:-18:4,gain:2\" (default: {})\n\
+         \x20 --tap <pre|post|n>  Where the live spectrum analyzes the chain: its input, its\n\
+         \x20                     output or after stage n, counting from 1 (default: post)\n\
+         \x20 --saturate <params> Append \"sat:<params>\" to the chain, also after a --chain\n\
+         \x20 --dehum <hz>        Append \"dehum:<hz>\" to remove mains hum, e.g. 50 or 60\n\
+         \x20 --fir <path>        Filter with the FIR taps in a text file after the chain; n\n\
+         \x20                     taps of a symmetric kernel delay by (n - 1) / 2 samples, over\n\
+         \x20                     64 taps by n rounded up to a power of two on top\n\
+         \x20 --skip-silence      Jump over long silent stretches during playback\n\
+         \x20 --skip-threshold <db>\n\
+         \x20                     Peak envelope level below which audio is silent (default: -50)\n\
+         \x20 --skip-gap <secs>   Shortest silent stretch that is skipped (default: 3)\n\
+         \x20 --automation <spec> Gain envelope of ms:dB points, e.g. \"0:-inf,1000:0\";\n\
+         \x20                     gain is interpolated linearly and -inf is silence\n\
+         \n\
+         Chain processors:\n\
+         {}\n\
+         \n\
+         Keys: space pause, q quit, r toggle reference, n next reference, c clear clip,\n\
+         \x20     e toggle energy share, o toggle octave colors, t toggle the target curve,\n\
+         \x20     v toggle bars and curve, b toggle the beat average,\n\
+         \x20     a switch the compared file, f toggle floor shading,\n\
+         \x20     s snapshot the bars and show the change since, x clear the snapshot,\n\
+         \x20     p bypass the processing chain (crossfaded over 20 ms),\n\
+         \x20     +/- double/halve the FFT size (256 to 32768),\n\
+         \x20     d toggle the difference to the dry signal, [/] previous/next track,\n\
+         \x20     left/right move the analysis window while paused",
+        program, program, WINDOW_NAMES, BUILTIN_THEMES, OSC_USAGE, DEFAULT_CHAIN, CHAIN_HELP
+    )
+}
+
+pub fn parse_args() -> Result<Options, String> {
+    let mut args = env::args();
+    let program = args.next().unwrap_or_else(|| "sound_heat".to_string());
+
+    let mut file_path = None;
+    // Chain tokens of the shorthands, appended once every argument is read so
+    // a --chain anywhere on the line doesn't replace them.
+    let mut appended_stages = Vec::new();
+    // Chain options given, which --input mic refuses as it plays nothing
+    // through the chain. Some of them leave no trace in `options`.
+    let mut chain_flags = Vec::new();
+    let mut options = Options {
+        file_path: String::new(),
+        mic: false,
+        input_device: None,
+        export: None,
+        bits: 16,
+        dither: None,
+        noise_shaping: false,
+        normalize_lufs: None,
+        max_freq: None,
+        references: Vec::new(),
+        compare: Vec::new(),
+        target: None,
+        band_smoothing: false,
+        zero_pad: 1,
+        chain: DEFAULT_CHAIN.to_string(),
+        fir: None,
+        tap: None,
+        midi: None,
+        loudness_graph: None,
+        spectrogram: None,
+        spec_width: 1600,
+        spec_height: 600,
+        spec_fft: 4096,
+        spec_overlap: 0.75,
+        spec_range: (-100.0, 0.0),
+        split_on_silence: None,
+        split_threshold_db: -50.0,
+        split_gap_secs: 2.0,
+        skip_silence: false,
+        cue: None,
+        split_tracks: None,
+        skip_threshold_db: -50.0,
+        skip_gap_secs: 3.0,
+        features: None,
+        chromagram: None,
+        channel_mode: ChannelMode::Mid,
+        silence_db: None,
+        meter_window_ms: loudness::SHORT_TERM_SECS * 1000.0,
+        peak_weighting: None,
+        input_gain_db: 0.0,
+        window: Window::Hann,
+        enbw_correction: false,
+        include_dc: false,
+        bar_scale: BarScale::Linear,
+        band_peaks: false,
+        hold_secs: None,
+        energy_share: false,
+        octave_colors: false,
+        curve: false,
+        floor_shading: false,
+        beat_average: false,
+        show_delay: false,
+        theme: None,
+        tuner: false,
+        a4: pitch::A4,
+        tuner_smoothing: 0.8,
+        preroll: false,
+        trim_lead: true,
+        interpolate: false,
+        fps: 60.0,
+        print_frames: false,
+        automation: None,
+        log: None,
+        log_interval: 1.0,
+        report: false,
+        expect: None,
+        fast_scan: 1,
+        #[cfg(feature = "osc")]
+        osc_target: None,
+        #[cfg(feature = "osc")]
+        osc_bands_address: "/sound-heat/bands".to_string(),
+        #[cfg(feature = "osc")]
+        osc_beat_address: "/sound-heat/beat".to_string(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                options.mic = match value(&mut args, &arg)?.as_str() {
+                    "file" => false,
+                    "mic" => true,
+                    other => {
+                        return Err(format!("Unknown input: {} (expected file or mic)", other));
+                    }
+                }
+            }
+            "--input-device" => options.input_device = Some(value(&mut args, &arg)?),
+            "--export" => options.export = Some(value(&mut args, &arg)?),
+            "--bits" => {
+                options.bits = match value(&mut args, &arg)?.as_str() {
+                    "16" => 16,
+                    "24" => 24,
+                    other => return Err(format!("Unsupported bit depth: {}", other)),
+                }
+            }
+            "--dither" => options.dither = Some(true),
+            "--no-dither" => options.dither = Some(false),
+            "--noise-shaping" => options.noise_shaping = true,
+            "--normalize-lufs" => options.normalize_lufs = Some(number(&mut args, &arg)?),
+            "--max-freq" => options.max_freq = Some(number(&mut args, &arg)?),
+            "--reference" => options.references.push(value(&mut args, &arg)?),
+            "--compare" => options.compare.push(value(&mut args, &arg)?),
+            "--target" => options.target = Some(value(&mut args, &arg)?),
+            #[cfg(feature = "osc")]
+            "--osc" => options.osc_target = Some(value(&mut args, &arg)?),
+            #[cfg(feature = "osc")]
+            "--osc-bands-address" => options.osc_bands_address = value(&mut args, &arg)?,
+            #[cfg(feature = "osc")]
+            "--osc-beat-address" => options.osc_beat_address = value(&mut args, &arg)?,
+            "--automation" => {
+                options.automation = Some(value(&mut args, &arg)?);
+                chain_flags.push("--automation");
+            }
+            "--log" => options.log = Some(value(&mut args, &arg)?),
+            "--log-interval" => {
+                let secs: f32 = number(&mut args, &arg)?;
+                if secs <= 0.0 {
+                    return Err(format!("{} must be positive", arg));
+                }
+                options.log_interval = secs;
+            }
+            "--print-frames" => options.print_frames = true,
+            "--preroll" => options.preroll = true,
+            "--no-trim-lead" => options.trim_lead = false,
+            "--interpolate" => options.interpolate = true,
+            "--fps" => {
+                let fps: f32 = number(&mut args, &arg)?;
+                if fps <= 0.0 {
+                    return Err(format!("{} must be positive", arg));
+                }
+                options.fps = fps;
+            }
+            "--band-peaks" => options.band_peaks = true,
+            "--hold" => {
+                let secs: f32 = number(&mut args, &arg)?;
+                if secs < 0.0 {
+                    return Err(format!("{} must not be negative", arg));
+                }
+                options.hold_secs = Some(secs);
+            }
+            "--energy-share" => options.energy_share = true,
+            "--octave-colors" => options.octave_colors = true,
+            "--curve" => options.curve = true,
+            "--floor-shading" => options.floor_shading = true,
+            "--beat-average" => options.beat_average = true,
+            "--show-delay" => options.show_delay = true,
+            "--theme" => options.theme = Some(value(&mut args, &arg)?),
+            "--tuner" => options.tuner = true,
+            "--a4" => {
+                let hz = number(&mut args, &arg)?;
+                if !hz.is_finite() || hz <= 0.0 {
+                    return Err(format!("{} must be a positive frequency in Hz", arg));
+                }
+                options.a4 = hz;
+            }
+            "--tuner-smoothing" => {
+                options.tuner_smoothing = number(&mut args, &arg)?.clamp(0.0, 0.99)
+            }
+            "--band-smoothing" => options.band_smoothing = true,
+            "--zero-pad" => {
+                let factor = number(&mut args, &arg)?;
+                if factor < 1.0 {
+                    return Err(format!("{} must be at least 1", arg));
+                }
+                options.zero_pad = factor as usize;
+            }
+            "--channel-mode" => {
+                let name = value(&mut args, &arg)?;
+                options.channel_mode = ChannelMode::from_name(&name)
+                    .ok_or_else(|| format!("Unknown channel mode: {}", name))?;
+            }
+            "--silence-db" => options.silence_db = Some(number(&mut args, &arg)?),
+            "--meter-window" => {
+                let ms: f32 = number(&mut args, &arg)?;
+                if ms <= 0.0 {
+                    return Err(format!("{} must be positive", arg));
+                }
+                options.meter_window_ms = ms;
+            }
+            "--peak-weighting" => {
+                let name = value(&mut args, &arg)?;
+                options.peak_weighting = Some(Weighting::from_name(&name).ok_or_else(|| {
+                    format!("Unknown peak weighting: {} (expected flat, a or k)", name)
+                })?);
+            }
+            "--input-gain" => options.input_gain_db = number(&mut args, &arg)?,
+            "--window" => {
+                let name = value(&mut args, &arg)?;
+                options.window = Window::from_name(&name).ok_or_else(|| {
+                    format!("Unknown window: {} (expected {})", name, WINDOW_NAMES)
+                })?;
+            }
+            "--enbw" => options.enbw_correction = true,
+            "--include-dc" => options.include_dc = true,
+            "--bar-scale" => options.bar_scale = BarScale::parse(&value(&mut args, &arg)?)?,
+            "--features" => options.features = Some(value(&mut args, &arg)?),
+            "--chromagram" => options.chromagram = Some(value(&mut args, &arg)?),
+            "--split-on-silence" => options.split_on_silence = Some(value(&mut args, &arg)?),
+            "--split-threshold" => options.split_threshold_db = number(&mut args, &arg)?,
+            "--split-gap" => {
+                let secs = number(&mut args, &arg)?;
+                if !secs.is_finite() || secs <= 0.0 {
+                    return Err(format!("{} must be a positive number of seconds", arg));
+                }
+                options.split_gap_secs = secs;
+            }
+            "--skip-silence" => options.skip_silence = true,
+            "--cue" => options.cue = Some(value(&mut args, &arg)?),
+            "--split-tracks" => options.split_tracks = Some(value(&mut args, &arg)?),
+            "--skip-threshold" => options.skip_threshold_db = number(&mut args, &arg)?,
+            "--skip-gap" => {
+                let secs = number(&mut args, &arg)?;
+                if !secs.is_finite() || secs <= 0.0 {
+                    return Err(format!("{} must be a positive number of seconds", arg));
+                }
+                options.skip_gap_secs = secs;
+            }
+            "--report" => options.report = true,
+            "--expect" => options.expect = Some(Expectation::parse(&value(&mut args, &arg)?)?),
+            "--fast-scan" => {
+                let step = number(&mut args, &arg)?;
+                if step < 1.0 {
+                    return Err(format!("{} must be at least 1", arg));
+                }
+                options.fast_scan = step as usize;
+                options.report = true;
+            }
+            "--loudness-graph" => options.loudness_graph = Some(value(&mut args, &arg)?),
+            "--spectrogram" => options.spectrogram = Some(value(&mut args, &arg)?),
+            "--spec-width" | "--spec-height" => {
+                let pixels = number(&mut args, &arg)?;
+                if !(16.0..=16384.0).contains(&pixels) {
+                    return Err(format!("{} must be from 16 to 16384 pixels", arg));
+                }
+                if arg == "--spec-width" {
+                    options.spec_width = pixels as usize;
+                } else {
+                    options.spec_height = pixels as usize;
+                }
+            }
+            "--spec-fft" => {
+                let size = number(&mut args, &arg)? as usize;
+                if !size.is_power_of_two() || !(256..=32768).contains(&size) {
+                    return Err(format!("{} must be a power of two from 256 to 32768", arg));
+                }
+                options.spec_fft = size;
+            }
+            "--spec-overlap" => {
+                let overlap = number(&mut args, &arg)?;
+                if !(0.0..=0.95).contains(&overlap) {
+                    return Err(format!("{} must be from 0 to 0.95", arg));
+                }
+                options.spec_overlap = overlap;
+            }
+            "--spec-range" => {
+                let raw = value(&mut args, &arg)?;
+                let range = raw
+                    .split_once(':')
+                    .and_then(|(min, max)| Some((min.parse().ok()?, max.parse().ok()?)));
+                match range {
+                    Some((min, max)) if min < max => options.spec_range = (min, max),
+                    _ => {
+                        return Err(format!(
+                            "Invalid range for {}: {} (expected <min>:<max> in dB)",
+                            arg, raw
+                        ));
+                    }
+                }
+            }
+            "--midi" => options.midi = Some(value(&mut args, &arg)?),
+            "--chain" => {
+                options.chain = value(&mut args, &arg)?;
+                chain_flags.push("--chain");
+            }
+            "--tap" => {
+                chain_flags.push("--tap");
+                let position = value(&mut args, &arg)?;
+                options.tap = match position.as_str() {
+                    "pre" => Some(0),
+                    "post" => None,
+                    _ => Some(position.parse().map_err(|_| {
+                        format!("{} must be pre, post or a stage number: {}", arg, position)
+                    })?),
+                };
+            }
+            // Shorthand for appending a saturator to the chain.
+            "--saturate" => {
+                chain_flags.push("--saturate");
+                appended_stages.push(format!("sat:{}", value(&mut args, &arg)?));
+            }
+            "--fir" => {
+                options.fir = Some(value(&mut args, &arg)?);
+                chain_flags.push("--fir");
+            }
+            "--dehum" => {
+                chain_flags.push("--dehum");
+                appended_stages.push(format!("dehum:{}", value(&mut args, &arg)?));
+            }
+            "-h" | "--help" => return Err(usage(&program)),
+            _ if arg.starts_with("--") => {
+                return Err(format!("Unknown option: {}\n\n{}", arg, usage(&program)));
+            }
+            _ if file_path.is_none() => file_path = Some(arg),
+            _ => {
+                return Err(format!(
+                    "Unexpected argument: {}\n\n{}",
+                    arg,
+                    usage(&program)
+                ));
+            }
+        }
+    }
+
+    for stage in appended_stages {
+        options.chain = format!("{},{}", options.chain, stage);
+    }
+    if options.split_tracks.is_some() && options.cue.is_none() {
+        return Err("--split-tracks needs a cue sheet, see --cue".to_string());
+    }
+    if options.normalize_lufs.is_some() && options.export.is_none() {
+        return Err("--normalize-lufs applies to --export".to_string());
+    }
+    // There is no FLAC encoder; refuse rather than write WAV data to a .flac.
+    if let Some(path) = &options.export
+        && path.to_lowercase().ends_with(".flac")
+    {
+        return Err(format!(
+            "--export only writes WAV, FLAC is not supported: {}",
+            path
+        ));
+    }
+    if options.mic {
+        if let Some(path) = file_path {
+            return Err(format!("--input mic takes no file, got {}", path));
+        }
+        let file_only = [
+            ("--export", options.export.is_some()),
+            ("--features", options.features.is_some()),
+            ("--chromagram", options.chromagram.is_some()),
+            ("--midi", options.midi.is_some()),
+            ("--loudness-graph", options.loudness_graph.is_some()),
+            ("--spectrogram", options.spectrogram.is_some()),
+            ("--split-on-silence", options.split_on_silence.is_some()),
+            ("--report", options.report),
+            ("--expect", options.expect.is_some()),
+            ("--cue", options.cue.is_some()),
+            ("--compare", !options.compare.is_empty()),
+            ("--beat-average", options.beat_average),
+            ("--skip-silence", options.skip_silence),
+        ];
+        let flag = file_only
+            .iter()
+            .find(|(_, set)| *set)
+            .map(|(flag, _)| flag)
+            .or(chain_flags.first());
+        if let Some(flag) = flag {
+            return Err(format!(
+                "{} needs a file and can't be used with --input mic",
+                flag
+            ));
+        }
+        return Ok(options);
+    }
+    if options.input_device.is_some() {
+        return Err("--input-device only applies to --input mic".to_string());
+    }
+    options.file_path = file_path.ok_or_else(|| usage(&program))?;
+    Ok(options)
+}
+
+fn value(args: &mut env::Args, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for {}", flag))
+}
+
+fn number(args: &mut env::Args, flag: &str) -> Result<f32, String> {
+    let raw = value(args, flag)?;
+    raw.parse()
+        .map_err(|_| format!("Invalid number for {}: {}", flag, raw))
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::loudness;
+
+// Files played in sync for an A/B comparison, gained to the same integrated
+// loudness so level differences don't sway the judgement. Matching is to the
+// quietest file, so nothing is boosted into clipping. Playback switches
+// between them through `selected`, see `ProcessedSource::play_versions`.
+pub struct Comparison {
+    pub names: Vec<String>,
+    pub lufs: Vec<f32>,
+    pub gains_db: Vec<f32>,
+    pub versions: Vec<Arc<Vec<f32>>>,
+    pub selected: Arc<AtomicUsize>,
+}
+
+impl Comparison {
+    // Takes every file's name and samples, all of the same layout and length.
+    pub fn new(
+        files: Vec<(String, Vec<f32>)>,
+        channels: usize,
+        sample_rate: u32,
+    ) -> Result<Self, String> {
+        let mut lufs = Vec::new();
+        for (name, samples) in &files {
+            let power = loudness::weighted_power(samples, channels, sample_rate);
+            let integrated = loudness::integrated(&power, sample_rate).ok_or_else(|| {
+                format!(
+                    "--compare: {} is too short or silent to measure its loudness",
+                    name
+                )
+            })?;
+            lufs.push(integrated);
+        }
+        let target = lufs.iter().copied().fold(f32::INFINITY, f32::min);
+        let gains_db: Vec<f32> = lufs.iter().map(|&l| target - l).collect();
+        let mut names = Vec::new();
+        let mut versions = Vec::new();
+        for ((name, mut samples), &gain_db) in files.into_iter().zip(&gains_db) {
+            let gain = 10f32.powf(gain_db / 20.0);
+            for s in samples.iter_mut() {
+                *s *= gain;
+            }
+            names.push(name);
+            versions.push(samples.into());
+        }
+        Ok(Comparison {
+            names,
+            lufs,
+            gains_db,
+            versions,
+            selected: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn current(&self) -> usize {
+        self.selected.load(Ordering::Relaxed)
+    }
+
+    // Switches playback to the next file, after the last back to the first.
+    pub fn next(&self) -> usize {
+        let index = (self.current() + 1) % self.versions.len();
+        self.selected.store(index, Ordering::Relaxed);
+        index
+    }
+
+    // Status line naming the file playing, e.g. "Comparing 2/2: b.wav at -3.1 dB".
+    pub fn status(&self) -> String {
+        let index = self.current();
+        format!(
+            "Comparing {}/{}: {} at {:+.1} dB",
+            index + 1,
+            self.versions.len(),
+            self.names[index],
+            self.gains_db[index]
+        )
+    }
+}
@@ -0,0 +1,202 @@
+use std::io::{Stdout, Write};
+
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use image::RgbImage;
+use symphonia::core::meta::{StandardVisualKey, Visual};
+
+use crate::decode;
+
+// Text rows taken by the cover art above the spectrum.
+pub const ART_ROWS: u16 = 12;
+
+// Size of the base64 chunks sent with the kitty protocol, its upper limit.
+const KITTY_CHUNK: usize = 4096;
+// Longest side of art sent to kitty as raw pixels, which is uncompressed.
+const KITTY_MAX_PIXELS: u32 = 512;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Inline image protocols. iTerm2 takes the encoded picture as is, kitty
+// takes PNG or raw pixels.
+enum Protocol {
+    Iterm,
+    Kitty,
+}
+
+// Embedded picture of a file, preferring the front cover. `None` when the
+// file has no art or can't be probed.
+pub fn load(path: &str) -> Option<Visual> {
+    let mut probed = decode::probe(path).ok()?;
+
+    // Tags in front of the container (ID3v2) and inside it are kept apart.
+    let mut visuals: Vec<Visual> = Vec::new();
+    if let Some(metadata) = probed.metadata.get()
+        && let Some(revision) = metadata.current()
+    {
+        visuals.extend_from_slice(revision.visuals());
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        visuals.extend_from_slice(revision.visuals());
+    }
+
+    let front = visuals
+        .iter()
+        .position(|v| v.usage == Some(StandardVisualKey::FrontCover))
+        .unwrap_or(0);
+    (front < visuals.len()).then(|| visuals.swap_remove(front))
+}
+
+// Draws the art at the top left corner, `rows` text rows high. Terminals
+// without an image protocol get it in half blocks, two pixels to a cell.
+// Returns false without printing anything when the picture can't be decoded.
+pub fn draw(art: &Visual, rows: u16, stdout: &mut Stdout) -> bool {
+    match protocol() {
+        Some(Protocol::Iterm) => {
+            write!(
+                stdout,
+                "\x1b[H\x1b]1337;File=inline=1;size={};height={};preserveAspectRatio=1:{}\x07",
+                art.data.len(),
+                rows,
+                base64(&art.data)
+            )
+            .unwrap();
+        }
+        Some(Protocol::Kitty) if art.media_type == "image/png" => {
+            kitty(stdout, "f=100", &art.data, rows);
+        }
+        // kitty only decodes PNG itself, anything else is sent as RGB.
+        Some(Protocol::Kitty) => {
+            let Some(image) = decode(&art.data, KITTY_MAX_PIXELS) else {
+                return false;
+            };
+            let format = format!("f=24,s={},v={}", image.width(), image.height());
+            kitty(stdout, &format, image.as_raw(), rows);
+        }
+        None => {
+            // Cells are about twice as tall as wide, so a half block is square.
+            let Some(image) = decode(&art.data, 2 * rows as u32) else {
+                return false;
+            };
+            half_blocks(&image, stdout);
+        }
+    }
+    stdout.flush().unwrap();
+    true
+}
+
+// The art as 8-bit RGB, scaled to fit a square of `size` pixels.
+fn decode(data: &[u8], size: u32) -> Option<RgbImage> {
+    let image = image::load_from_memory(data).ok()?;
+    Some(image.thumbnail(size, size).to_rgb8())
+}
+
+// Sends pixel data in the kitty format given by the `format` keys.
+fn kitty(stdout: &mut Stdout, format: &str, data: &[u8], rows: u16) {
+    write!(stdout, "\x1b[H").unwrap();
+    let encoded = base64(data);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        let chunk = std::str::from_utf8(chunk).unwrap();
+        if i == 0 {
+            write!(
+                stdout,
+                "\x1b_Ga=T,{},C=1,r={},m={};{}\x1b\\",
+                format, rows, more, chunk
+            )
+            .unwrap();
+        } else {
+            write!(stdout, "\x1b_Gm={};{}\x1b\\", more, chunk).unwrap();
+        }
+    }
+}
+
+// Every cell is an upper half block in the colour of the top pixel on the
+// colour of the one below. An odd last row keeps the terminal's background.
+fn half_blocks(image: &RgbImage, out: &mut impl Write) {
+    let rgb = |x, y| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        Color::Rgb { r, g, b }
+    };
+    for y in (0..image.height()).step_by(2) {
+        queue!(out, MoveTo(0, (y / 2) as u16)).unwrap();
+        for x in 0..image.width() {
+            queue!(out, SetForegroundColor(rgb(x, y))).unwrap();
+            if y + 1 < image.height() {
+                queue!(out, SetBackgroundColor(rgb(x, y + 1))).unwrap();
+            }
+            queue!(out, Print('\u{2580}')).unwrap();
+        }
+        queue!(out, ResetColor).unwrap();
+    }
+}
+
+// There is no reliable query for image support, so go by the variables the
+// terminals set.
+fn protocol() -> Option<Protocol> {
+    let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    if program == "iTerm.app" || program == "WezTerm" {
+        return Some(Protocol::Iterm);
+    }
+    if std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || std::env::var("TERM").is_ok_and(|term| term == "xterm-kitty")
+    {
+        return Some(Protocol::Kitty);
+    }
+    None
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(triple >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 40 x 20 gradient encoded in `format`.
+    fn encoded(format: image::ImageFormat) -> Vec<u8> {
+        let image = RgbImage::from_fn(40, 20, |x, y| image::Rgb([x as u8 * 6, y as u8 * 12, 128]));
+        let mut data = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn decodes_png_and_jpeg_to_fit() {
+        for format in [image::ImageFormat::Png, image::ImageFormat::Jpeg] {
+            let image = decode(&encoded(format), 8).unwrap();
+            assert_eq!((image.width(), image.height()), (8, 4), "{:?}", format);
+        }
+        assert!(decode(b"not an image", 8).is_none());
+    }
+
+    #[test]
+    fn half_blocks_cover_two_pixel_rows_per_line() {
+        let image = RgbImage::from_pixel(3, 5, image::Rgb([255, 0, 0]));
+        let mut out = Vec::new();
+        half_blocks(&image, &mut out);
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.matches('\u{2580}').count(), 3 * 3);
+        assert_eq!(text.matches("\x1b[3;1H").count(), 1);
+        assert!(!text.contains("\x1b[4;1H"));
+    }
+}
@@ -0,0 +1,151 @@
+// CD frames per second, the unit of the last field of cue sheet timestamps.
+const FRAMES_PER_SEC: f32 = 75.0;
+
+pub struct Track {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    // Position of INDEX 01, where the track starts, in seconds.
+    pub start_secs: f32,
+}
+
+pub struct CueSheet {
+    pub tracks: Vec<Track>,
+}
+
+impl CueSheet {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read cue sheet {}: {}", path, e))?;
+        CueSheet::parse(&text).map_err(|e| format!("Invalid cue sheet {}: {}", path, e))
+    }
+
+    // Reads the TRACK, TITLE, PERFORMER and INDEX 01 commands of a cue sheet
+    // for a single audio file; other commands are ignored. Titles before the
+    // first track name the album and are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut tracks: Vec<Track> = Vec::new();
+        let mut files = 0;
+        let mut has_index = false;
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let line = line.trim();
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let rest = rest.trim();
+            match command {
+                "FILE" => {
+                    files += 1;
+                    if files > 1 {
+                        return Err(error("cue sheets for several files are not supported"));
+                    }
+                }
+                "TRACK" => {
+                    if let Some(track) = tracks.last()
+                        && !has_index
+                    {
+                        return Err(error(&format!("track {} has no INDEX 01", track.number)));
+                    }
+                    let number = rest
+                        .split_whitespace()
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .ok_or_else(|| error("expected a track number"))?;
+                    tracks.push(Track {
+                        number,
+                        title: None,
+                        performer: None,
+                        start_secs: 0.0,
+                    });
+                    has_index = false;
+                }
+                "TITLE" | "PERFORMER" => {
+                    if let Some(track) = tracks.last_mut() {
+                        let value = Some(rest.trim_matches('"').to_string());
+                        if command == "TITLE" {
+                            track.title = value;
+                        } else {
+                            track.performer = value;
+                        }
+                    }
+                }
+                "INDEX" => {
+                    let (index, time) = rest
+                        .split_once(' ')
+                        .ok_or_else(|| error("expected INDEX <number> <mm:ss:ff>"))?;
+                    if index.parse::<u32>() != Ok(1) {
+                        continue;
+                    }
+                    let track = tracks
+                        .last_mut()
+                        .ok_or_else(|| error("INDEX before the first TRACK"))?;
+                    track.start_secs =
+                        parse_time(time.trim()).ok_or_else(|| error("expected mm:ss:ff"))?;
+                    has_index = true;
+                }
+                _ => {}
+            }
+        }
+        if tracks.is_empty() {
+            return Err("no tracks".to_string());
+        }
+        if !has_index {
+            let number = tracks[tracks.len() - 1].number;
+            return Err(format!("track {} has no INDEX 01", number));
+        }
+        if tracks
+            .windows(2)
+            .any(|pair| pair[1].start_secs < pair[0].start_secs)
+        {
+            return Err("tracks are not in order".to_string());
+        }
+        Ok(CueSheet { tracks })
+    }
+
+    // Frame range of every track, each running up to the start of the next,
+    // so pregaps belong to the track before them. `frames_per_sec` is the
+    // sample rate, or less if only part of the file was decoded.
+    pub fn frame_ranges(&self, frames_per_sec: f32, total_frames: usize) -> Vec<(usize, usize)> {
+        let starts: Vec<usize> = self
+            .tracks
+            .iter()
+            .map(|track| ((track.start_secs * frames_per_sec) as usize).min(total_frames))
+            .collect();
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| (start, starts.get(i + 1).copied().unwrap_or(total_frames)))
+            .collect()
+    }
+
+    // Index of the track playing at `secs`.
+    pub fn track_at(&self, secs: f32) -> usize {
+        self.tracks
+            .iter()
+            .rposition(|track| track.start_secs <= secs)
+            .unwrap_or(0)
+    }
+}
+
+impl Track {
+    // "03 Performer - Title", leaving out what the sheet doesn't give.
+    pub fn label(&self) -> String {
+        let mut label = format!("{:02}", self.number);
+        if let Some(performer) = &self.performer {
+            label.push_str(&format!(" {} -", performer));
+        }
+        if let Some(title) = &self.title {
+            label.push_str(&format!(" {}", title));
+        }
+        label
+    }
+}
+
+// mm:ss:ff, where minutes can exceed 59 and ff counts 1/75 s.
+fn parse_time(time: &str) -> Option<f32> {
+    let mut fields = time.split(':').map(|field| field.parse::<u32>().ok());
+    let (minutes, seconds, frames) = (fields.next()??, fields.next()??, fields.next()??);
+    if fields.next().is_some() || seconds >= 60 || frames as f32 >= FRAMES_PER_SEC {
+        return None;
+    }
+    Some((minutes * 60 + seconds) as f32 + frames as f32 / FRAMES_PER_SEC)
+}
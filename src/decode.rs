@@ -0,0 +1,457 @@
+use std::fs::File;
+use std::path::Path;
+
+use symphonia::core::audio::{AudioBufferRef, SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{self, CodecParameters, Decoder, DecoderOptions};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::{Hint, ProbeResult};
+
+pub struct Decoded {
+    // Interleaved samples in [-1, 1].
+    pub samples: Vec<f32>,
+    pub channels: usize,
+    pub sample_rate: u32,
+    // Sample format stored in the file, e.g. "24-bit integer", if known.
+    pub sample_format: Option<String>,
+    // Packets read from the file and how many of them were decoded.
+    pub packets: usize,
+    pub decoded_packets: usize,
+    // Times the sample rate or channel count changed mid-stream, and how
+    // many seconds were converted to the dominant one, see `decode_file`.
+    pub format_changes: usize,
+    pub converted_secs: f32,
+}
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// Opens a file or URL with symphonia, using the extension as a format hint.
+pub fn probe(path: &str) -> Result<ProbeResult, String> {
+    let mut hint = Hint::new();
+    let source: Box<dyn MediaSource> = if is_url(path) {
+        open_url(path, &mut hint)?
+    } else {
+        Box::new(File::open(path).map_err(|e| format!("Failed to open file: {}", e))?)
+    };
+    let stream = MediaSourceStream::new(source, Default::default());
+    let name = path.split(['?', '#']).next().unwrap_or(path);
+    if let Some(extension) = Path::new(name).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| format!("Failed to decode audio: {}", e))
+}
+
+// Streams the response body. It can't seek, which symphonia handles by
+// reading the format front to back.
+#[cfg(feature = "http")]
+fn open_url(url: &str, hint: &mut Hint) -> Result<Box<dyn MediaSource>, String> {
+    let stream = crate::http::get(url)?;
+    if let Some(content_type) = &stream.content_type {
+        hint.mime_type(content_type);
+    }
+    Ok(Box::new(symphonia::core::io::ReadOnlySource::new(stream)))
+}
+
+#[cfg(not(feature = "http"))]
+fn open_url(url: &str, _hint: &mut Hint) -> Result<Box<dyn MediaSource>, String> {
+    Err(format!(
+        "Can't open {}: reading URLs needs the http feature",
+        url
+    ))
+}
+
+// Sample rate and channel count of the default track, from the container
+// headers where they are given, so usually nothing has to be decoded.
+// Otherwise only the first packet is.
+pub fn probe_format(path: &str) -> Result<(u32, usize), String> {
+    let mut format = probe(path)?.format;
+    let track = format
+        .default_track()
+        .ok_or_else(|| "No audio track found".to_string())?;
+    let params = &track.codec_params;
+    if let (Some(rate), Some(channels)) = (params.sample_rate, params.channels) {
+        return Ok((rate, channels.count()));
+    }
+    let track_id = track.id;
+    let mut decoder = make_decoder(params)?;
+    loop {
+        let packet = format
+            .next_packet()
+            .map_err(|e| format!("Failed to read packet: {}", e))?;
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(buffer) => {
+                let spec = buffer.spec();
+                return Ok((spec.rate, spec.channels.count()));
+            }
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("Failed to decode audio: {}", e)),
+        }
+    }
+}
+
+// Decodes the default track of a file. With a `packet_step` above 1 only every
+// Nth packet is decoded and the others are skipped after reading, which is
+// much faster for compressed formats. The result is then an estimate:
+// levels and spectra describe a sample of the track rather than all of it,
+// short events between decoded packets are missed, and the joins between
+// packets add some spectral leakage. Codecs with inter-frame state, like the
+// MP3 bit reservoir, may also produce a short glitch after every skip.
+//
+// Broken or concatenated files may change the sample rate or channel count
+// between packets. The result then keeps the spec that covers most of the
+// audio and the rest is remixed and resampled to it, so the timing holds.
+pub fn decode_file(path: &str, packet_step: usize) -> Result<Decoded, String> {
+    let mut packets = PacketDecoder::open(path, packet_step)?;
+    // Runs of consecutive packets that share a spec.
+    let mut runs: Vec<(SignalSpec, Vec<f32>)> = Vec::new();
+    while let Some((buffer_spec, buffer_samples)) = packets.next()? {
+        match runs.last_mut() {
+            Some((spec, samples)) if *spec == buffer_spec => {
+                samples.extend_from_slice(&buffer_samples);
+            }
+            _ => runs.push((buffer_spec, buffer_samples)),
+        }
+    }
+
+    let secs = |spec: &SignalSpec, samples: &[f32]| {
+        samples.len() as f32 / spec.channels.count() as f32 / spec.rate as f32
+    };
+    let mut total_secs: Vec<(SignalSpec, f32)> = Vec::new();
+    for (spec, samples) in &runs {
+        match total_secs.iter_mut().find(|(s, _)| s == spec) {
+            Some((_, total)) => *total += secs(spec, samples),
+            None => total_secs.push((*spec, secs(spec, samples))),
+        }
+    }
+    let (spec, _) = total_secs
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .copied()
+        .ok_or_else(|| "No audio decoded".to_string())?;
+    let channels = spec.channels.count();
+
+    let mut samples = Vec::new();
+    let mut converted_secs = 0.0;
+    for (run_spec, run) in &runs {
+        if *run_spec == spec {
+            samples.extend_from_slice(run);
+            continue;
+        }
+        converted_secs += secs(run_spec, run);
+        let remixed = remix(run, run_spec.channels.count(), channels);
+        samples.extend(resample(&remixed, channels, run_spec.rate, spec.rate));
+    }
+    Ok(Decoded {
+        samples,
+        channels,
+        sample_rate: spec.rate,
+        sample_format: packets.sample_format,
+        packets: packets.packets,
+        decoded_packets: packets.decoded_packets,
+        format_changes: runs.len() - 1,
+        converted_secs,
+    })
+}
+
+// Reads the default track packet by packet and decodes every
+// `packet_step`th one.
+struct PacketDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    params: CodecParameters,
+    track_id: u32,
+    packet_step: usize,
+    sample_format: Option<String>,
+    last_spec: Option<SignalSpec>,
+    packets: usize,
+    decoded_packets: usize,
+}
+
+impl PacketDecoder {
+    fn open(path: &str, packet_step: usize) -> Result<Self, String> {
+        let format = probe(path)?.format;
+        let track = format
+            .default_track()
+            .ok_or_else(|| "No audio track found".to_string())?;
+        let params = track.codec_params.clone();
+        Ok(PacketDecoder {
+            track_id: track.id,
+            sample_format: describe_sample_format(&params),
+            decoder: make_decoder(&params)?,
+            format,
+            params,
+            packet_step: packet_step.max(1),
+            last_spec: None,
+            packets: 0,
+            decoded_packets: 0,
+        })
+    }
+
+    // The spec and interleaved samples of the next decoded packet, `None` at
+    // the end of the track.
+    fn next(&mut self) -> Result<Option<(SignalSpec, Vec<f32>)>, String> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(format!("Failed to read packet: {}", e)),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            self.packets += 1;
+            if !(self.packets - 1).is_multiple_of(self.packet_step) {
+                continue;
+            }
+            let mut result = self.decoder.decode(&packet).map(interleave);
+            // Decoders like the MP3 one reject packets of another spec than
+            // the first, which a fresh decoder takes.
+            if let Err(Error::DecodeError(_)) = result
+                && let Some(last_spec) = self.last_spec
+            {
+                let mut fresh = make_decoder(&self.params)?;
+                if let Ok((spec, samples)) = fresh.decode(&packet).map(interleave)
+                    && spec != last_spec
+                {
+                    self.decoder = fresh;
+                    result = Ok((spec, samples));
+                }
+            }
+            match result {
+                Ok((spec, samples)) => {
+                    self.last_spec = Some(spec);
+                    self.decoded_packets += 1;
+                    return Ok(Some((spec, samples)));
+                }
+                // A corrupt packet only loses its own samples.
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(format!("Failed to decode audio: {}", e)),
+            }
+        }
+    }
+}
+
+fn make_decoder(params: &CodecParameters) -> Result<Box<dyn Decoder>, String> {
+    symphonia::default::get_codecs()
+        .make(params, &DecoderOptions::default())
+        .map_err(|e| format!("Unsupported codec: {}", e))
+}
+
+// Decodes a file or URL while it is played, for streams that may never end.
+// The first packet fixes the sample rate and channel count; later packets of
+// another spec are remixed and resampled to it, as in `decode_file`.
+pub struct StreamDecoder {
+    packets: PacketDecoder,
+    pub channels: usize,
+    pub sample_rate: u32,
+    first: Option<Vec<f32>>,
+}
+
+impl StreamDecoder {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mut packets = PacketDecoder::open(path, 1)?;
+        let (spec, first) = packets
+            .next()?
+            .ok_or_else(|| "No audio decoded".to_string())?;
+        Ok(StreamDecoder {
+            packets,
+            channels: spec.channels.count(),
+            sample_rate: spec.rate,
+            first: Some(first),
+        })
+    }
+
+    // Interleaved samples of the next packet, `None` at the end.
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<f32>>, String> {
+        if let Some(first) = self.first.take() {
+            return Ok(Some(first));
+        }
+        let Some((spec, samples)) = self.packets.next()? else {
+            return Ok(None);
+        };
+        if spec.rate == self.sample_rate && spec.channels.count() == self.channels {
+            return Ok(Some(samples));
+        }
+        let remixed = remix(&samples, spec.channels.count(), self.channels);
+        Ok(Some(resample(
+            &remixed,
+            self.channels,
+            spec.rate,
+            self.sample_rate,
+        )))
+    }
+}
+
+fn interleave(buffer: AudioBufferRef) -> (SignalSpec, Vec<f32>) {
+    let spec = *buffer.spec();
+    let mut samples = SampleBuffer::<f32>::new(buffer.capacity() as u64, spec);
+    samples.copy_interleaved_ref(buffer);
+    (spec, samples.samples().to_vec())
+}
+
+// Converts interleaved samples to another channel count. Mono is copied to
+// every channel and a mono result is the average of all channels; otherwise
+// channels are kept by position and missing ones are left silent.
+fn remix(samples: &[f32], from: usize, to: usize) -> Vec<f32> {
+    if from == to {
+        return samples.to_vec();
+    }
+    samples
+        .chunks_exact(from)
+        .flat_map(|frame| {
+            (0..to).map(move |channel| match (from, to) {
+                (1, _) => frame[0],
+                (_, 1) => frame.iter().sum::<f32>() / from as f32,
+                _ => frame.get(channel).copied().unwrap_or(0.0),
+            })
+        })
+        .collect()
+}
+
+// Linear interpolation between frames. It doesn't filter, so content above
+// the new Nyquist frequency aliases when downsampling; good enough for the
+// short stretches of a broken file it's used for.
+fn resample(samples: &[f32], channels: usize, from: u32, to: u32) -> Vec<f32> {
+    let frames = samples.len() / channels;
+    if from == to || frames == 0 {
+        return samples.to_vec();
+    }
+    let output_frames = (frames as u64 * to as u64 / from as u64) as usize;
+    let mut output = Vec::with_capacity(output_frames * channels);
+    for frame in 0..output_frames {
+        let position = frame as f64 * from as f64 / to as f64;
+        let index = position as usize;
+        let fraction = (position - index as f64) as f32;
+        let next = (index + 1).min(frames - 1);
+        for channel in 0..channels {
+            let a = samples[index * channels + channel];
+            let b = samples[next * channels + channel];
+            output.push(a + (b - a) * fraction);
+        }
+    }
+    output
+}
+
+// Every PCM format is scaled to [-1, 1] by symphonia: unsigned samples are
+// offset by half their range first and floats are taken as they are.
+fn describe_sample_format(params: &CodecParameters) -> Option<String> {
+    let float_bits = match params.codec {
+        codecs::CODEC_TYPE_PCM_F32LE | codecs::CODEC_TYPE_PCM_F32BE => Some(32),
+        codecs::CODEC_TYPE_PCM_F64LE | codecs::CODEC_TYPE_PCM_F64BE => Some(64),
+        _ => None,
+    };
+    let unsigned = matches!(
+        params.codec,
+        codecs::CODEC_TYPE_PCM_U8
+            | codecs::CODEC_TYPE_PCM_U16LE
+            | codecs::CODEC_TYPE_PCM_U16BE
+            | codecs::CODEC_TYPE_PCM_U24LE
+            | codecs::CODEC_TYPE_PCM_U24BE
+            | codecs::CODEC_TYPE_PCM_U32LE
+            | codecs::CODEC_TYPE_PCM_U32BE
+    );
+    match (float_bits, params.bits_per_sample) {
+        (Some(bits), _) => Some(format!("{}-bit float", bits)),
+        (None, Some(bits)) if unsigned => Some(format!("{}-bit unsigned integer", bits)),
+        (None, Some(bits)) => Some(format!("{}-bit integer", bits)),
+        // Lossy codecs have no bit depth.
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes `samples` of one format to a mono WAV in the temp directory.
+    fn fixture<S: hound::Sample + Copy>(
+        name: &str,
+        bits: u16,
+        format: hound::SampleFormat,
+        samples: &[S],
+    ) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "sound_heat_decode_{}_{}.wav",
+            std::process::id(),
+            name
+        ));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: bits,
+            sample_format: format,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for &s in samples {
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    // Decodes the fixture and checks it reads negative full scale, half
+    // scale and positive full scale in that order.
+    fn check(path: std::path::PathBuf, positive_full_scale: f32, format: &str) {
+        let decoded = decode_file(path.to_str().unwrap(), 1).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(decoded.channels, 1);
+        assert_eq!(decoded.sample_format.as_deref(), Some(format));
+        assert_eq!(decoded.samples.len(), 3);
+        assert!(decoded.samples.iter().all(|s| (-1.0..=1.0).contains(s)));
+        let expected = [-1.0, 0.5, positive_full_scale];
+        for (&got, want) in decoded.samples.iter().zip(expected) {
+            assert!((got - want).abs() < 1e-6, "{}: {} != {}", format, got, want);
+        }
+    }
+
+    #[test]
+    fn decodes_8_bit_to_unit_range() {
+        let path = fixture("8", 8, hound::SampleFormat::Int, &[i8::MIN, 64, i8::MAX]);
+        check(path, 127.0 / 128.0, "8-bit unsigned integer");
+    }
+
+    #[test]
+    fn decodes_16_bit_to_unit_range() {
+        let path = fixture(
+            "16",
+            16,
+            hound::SampleFormat::Int,
+            &[i16::MIN, 1 << 14, i16::MAX],
+        );
+        check(path, 32767.0 / 32768.0, "16-bit integer");
+    }
+
+    #[test]
+    fn decodes_24_bit_to_unit_range() {
+        let max = (1 << 23) - 1;
+        let path = fixture(
+            "24",
+            24,
+            hound::SampleFormat::Int,
+            &[-max - 1, 1 << 22, max],
+        );
+        check(path, max as f32 / (1 << 23) as f32, "24-bit integer");
+    }
+
+    #[test]
+    fn decodes_32_bit_float_to_unit_range() {
+        let path = fixture("f32", 32, hound::SampleFormat::Float, &[-1.0f32, 0.5, 1.0]);
+        check(path, 1.0, "32-bit float");
+    }
+}
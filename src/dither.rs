@@ -0,0 +1,97 @@
+pub struct Ditherer {
+    pub enabled: bool,
+    pub noise_shaping: bool,
+    scale: f32,
+    min: i32,
+    max: i32,
+    errors: Vec<f32>,
+    rng: u32,
+}
+
+impl Ditherer {
+    pub fn new(bits: u16, channels: usize, enabled: bool, noise_shaping: bool) -> Self {
+        let scale = (1i64 << (bits - 1)) as f32;
+        Ditherer {
+            enabled,
+            noise_shaping,
+            scale,
+            min: -(1i32 << (bits - 1)),
+            max: (1i32 << (bits - 1)) - 1,
+            errors: vec![0.0; channels],
+            rng: 0x9E37_79B9,
+        }
+    }
+
+    // Converts a sample in [-1, 1] to an integer of the configured bit depth.
+    // TPDF dither is the sum of two uniform values of +-0.5 LSB, which
+    // decorrelates the quantization error from the signal. With noise shaping
+    // the previous error is fed back so the noise is pushed towards high
+    // frequencies, where the ear is less sensitive.
+    pub fn quantize(&mut self, channel: usize, sample: f32) -> i32 {
+        let mut value = sample * self.scale;
+        if self.noise_shaping {
+            value -= self.errors[channel];
+        }
+        let dither = if self.enabled {
+            self.uniform() + self.uniform()
+        } else {
+            0.0
+        };
+        let quantized = (value + dither)
+            .round()
+            .clamp(self.min as f32, self.max as f32);
+        if self.noise_shaping {
+            self.errors[channel] = quantized - value;
+        }
+        quantized as i32
+    }
+
+    // Uniform value in [-0.5, 0.5) from a xorshift generator.
+    fn uniform(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f32 / u32::MAX as f32 - 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 200_000;
+
+    fn rms(values: impl Iterator<Item = f32>) -> f32 {
+        (values.map(|v| v * v).sum::<f32>() / SAMPLES as f32).sqrt()
+    }
+
+    #[test]
+    fn tpdf_dither_rms_is_lsb_over_root_6() {
+        // Two uniform values of variance 1/12 LSB^2 each add to 1/6.
+        let mut ditherer = Ditherer::new(16, 1, true, false);
+        let dither = rms((0..SAMPLES).map(|_| ditherer.uniform() + ditherer.uniform()));
+        assert!((dither - 1.0 / 6f32.sqrt()).abs() < 0.01, "{} LSB", dither);
+    }
+
+    #[test]
+    fn dithered_16_bit_error_is_half_an_lsb() {
+        // Rounding adds 1/12 LSB^2 to the dither's 1/6, 1/4 in total,
+        // whatever the signal.
+        let signal = |n: usize| 0.3 * (n as f32 * 0.001).sin();
+        let error = |dither: bool| {
+            let mut ditherer = Ditherer::new(16, 1, dither, false);
+            rms((0..SAMPLES).map(|n| {
+                let ideal = signal(n) * 32768.0;
+                ditherer.quantize(0, signal(n)) as f32 - ideal
+            }))
+        };
+        let dithered = error(true);
+        assert!((dithered - 0.5).abs() < 0.01, "{} LSB", dithered);
+        let undithered = error(false);
+        assert!(
+            (undithered - 1.0 / 12f32.sqrt()).abs() < 0.01,
+            "{} LSB",
+            undithered
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use crate::source::BlockProcessor;
+
+// Feed-forward compressor with a peak envelope follower in the dB domain.
+pub struct CompressorProcessor {
+    pub threshold_db: f32,
+    pub ratio: f32,
+    attack: f32,
+    release: f32,
+    envelope_db: f32,
+}
+
+impl CompressorProcessor {
+    pub fn new(
+        threshold_db: f32,
+        ratio: f32,
+        attack_ms: f32,
+        release_ms: f32,
+        sample_rate: u32,
+    ) -> Self {
+        CompressorProcessor {
+            threshold_db,
+            ratio,
+            attack: time_constant(attack_ms, sample_rate),
+            release: time_constant(release_ms, sample_rate),
+            envelope_db: -120.0,
+        }
+    }
+}
+
+// One-pole coefficient that reaches ~63% of a step after `ms` milliseconds.
+pub fn time_constant(ms: f32, sample_rate: u32) -> f32 {
+    (-1.0 / (ms.max(0.01) * 0.001 * sample_rate as f32)).exp()
+}
+
+impl BlockProcessor for CompressorProcessor {
+    fn process_sample(&mut self, input: f32) -> Option<f32> {
+        let level_db = 20.0 * (input.abs() + 1e-10).log10();
+        let coeff = if level_db > self.envelope_db {
+            self.attack
+        } else {
+            self.release
+        };
+        self.envelope_db = coeff * self.envelope_db + (1.0 - coeff) * level_db;
+
+        let over = self.envelope_db - self.threshold_db;
+        let reduction_db = if over > 0.0 {
+            over / self.ratio - over
+        } else {
+            0.0
+        };
+        Some(input * 10f32.powf(reduction_db / 20.0))
+    }
+}
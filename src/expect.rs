@@ -0,0 +1,65 @@
+// Accepted sample rates and channel counts of a file, for `--expect`. An
+// empty list accepts any value.
+pub struct Expectation {
+    pub sample_rates: Vec<u32>,
+    pub channels: Vec<usize>,
+}
+
+impl Expectation {
+    // `<rates>[/<channels>]`, each a `|` separated list of alternatives,
+    // e.g. `44100|48000/2`. A `*` accepts any value.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (rates, channels) = spec.split_once('/').unwrap_or((spec, "*"));
+        let invalid = || {
+            format!(
+                "Invalid expectation '{}': expected e.g. 44100|48000/2",
+                spec
+            )
+        };
+        Ok(Expectation {
+            sample_rates: parse_list(rates).ok_or_else(invalid)?,
+            channels: parse_list(channels).ok_or_else(invalid)?,
+        })
+    }
+
+    // Describes every mismatch; `Ok` if the file is as expected.
+    pub fn check(&self, sample_rate: u32, channels: usize) -> Result<(), String> {
+        let mut problems = Vec::new();
+        if !self.sample_rates.is_empty() && !self.sample_rates.contains(&sample_rate) {
+            problems.push(format!(
+                "sample rate is {} Hz, expected {}",
+                sample_rate,
+                join(&self.sample_rates, " or ")
+            ));
+        }
+        if !self.channels.is_empty() && !self.channels.contains(&channels) {
+            problems.push(format!(
+                "{} channels, expected {}",
+                channels,
+                join(&self.channels, " or ")
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+fn parse_list<T: std::str::FromStr>(list: &str) -> Option<Vec<T>> {
+    if list.trim() == "*" {
+        return Some(Vec::new());
+    }
+    list.split('|')
+        .map(|value| value.trim().parse().ok())
+        .collect()
+}
+
+fn join<T: ToString>(values: &[T], separator: &str) -> String {
+    values
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
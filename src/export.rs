@@ -0,0 +1,28 @@
+use crate::dither::Ditherer;
+
+pub fn write_wav(
+    path: &str,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    bits: u16,
+    ditherer: &mut Ditherer,
+) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: bits,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)
+        .map_err(|e| format!("Failed to create WAV file: {}", e))?;
+    for (i, &s) in samples.iter().enumerate() {
+        let value = ditherer.quantize(i % channels as usize, s);
+        writer
+            .write_sample(value)
+            .map_err(|e| format!("Failed to write sample: {}", e))?;
+    }
+    writer
+        .finalize()
+        .map_err(|e| format!("Failed to finalize WAV file: {}", e))
+}
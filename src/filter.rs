@@ -1,3 +1,4 @@
+use crate::biquad::Biquad;
 use crate::source::BlockProcessor;
 
 pub struct LowPassFilterBlockProcessor {
@@ -7,10 +8,6 @@ pub struct LowPassFilterBlockProcessor {
 }
 
 impl BlockProcessor for LowPassFilterBlockProcessor {
-    fn block_size(&self) -> usize {
-        1
-    }
-
     fn process_sample(&mut self, input: f32) -> Option<f32> {
         let rc = 1.0 / (2.0 * std::f32::consts::PI * self.cutoff);
         let dt = 1.0 / self.sample_rate as f32;
@@ -20,3 +17,92 @@ impl BlockProcessor for LowPassFilterBlockProcessor {
         Some(output)
     }
 }
+
+pub struct HighPassFilterBlockProcessor {
+    pub prev_input: f32,
+    pub prev_output: f32,
+    pub cutoff: f32,
+    pub sample_rate: u32,
+}
+
+impl BlockProcessor for HighPassFilterBlockProcessor {
+    fn process_sample(&mut self, input: f32) -> Option<f32> {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * self.cutoff);
+        let dt = 1.0 / self.sample_rate as f32;
+        let alpha = rc / (rc + dt);
+        let output = alpha * (self.prev_output + input - self.prev_input);
+        self.prev_input = input;
+        self.prev_output = output;
+        Some(output)
+    }
+}
+
+// Removes mains hum with a cascade of notches at the fundamental and its
+// harmonics, e.g. 50, 100, 150 and 200 Hz for a 50 Hz base.
+pub struct HumRemover {
+    notches: Vec<Biquad>,
+}
+
+impl HumRemover {
+    pub fn new(base_freq: f32, harmonics: usize, q: f32, sample_rate: u32) -> Self {
+        let nyquist = sample_rate as f32 / 2.0;
+        HumRemover {
+            notches: (1..=harmonics)
+                .map(|h| base_freq * h as f32)
+                .take_while(|&freq| freq < nyquist)
+                .map(|freq| Biquad::notch(freq, q, sample_rate))
+                .collect(),
+        }
+    }
+}
+
+impl BlockProcessor for HumRemover {
+    fn process_sample(&mut self, input: f32) -> Option<f32> {
+        Some(
+            self.notches
+                .iter_mut()
+                .fold(input, |sample, notch| notch.process(sample)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    // Level change in dB of a sine at `freq` through the hum remover, once
+    // the notches have settled.
+    fn gain_db(base_freq: f32, freq: f32) -> f32 {
+        let mut remover = HumRemover::new(base_freq, 4, 30.0, SAMPLE_RATE);
+        let sine: Vec<f32> = (0..2 * SAMPLE_RATE)
+            .map(|n| (2.0 * std::f32::consts::PI * freq * n as f32 / SAMPLE_RATE as f32).sin())
+            .collect();
+        let output: Vec<f32> = sine
+            .iter()
+            .map(|&s| remover.process_sample(s).unwrap())
+            .collect();
+        let settled = SAMPLE_RATE as usize;
+        analysis::to_db(analysis::rms(&output[settled..]) / analysis::rms(&sine[settled..]))
+    }
+
+    #[test]
+    fn removes_hum_and_its_harmonics() {
+        for base in [50.0, 60.0] {
+            for harmonic in 1..=4 {
+                let freq = base * harmonic as f32;
+                assert!(gain_db(base, freq) < -30.0, "{} Hz", freq);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_nearby_frequencies() {
+        for freq in [75.0, 440.0] {
+            assert!(gain_db(50.0, freq).abs() < 0.5, "{} Hz", freq);
+        }
+        assert!(gain_db(60.0, 90.0).abs() < 0.5);
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+use crate::source::BlockProcessor;
+
+// Longest kernel accepted, about 1.4 s at 48 kHz.
+pub const MAX_TAPS: usize = 65536;
+// Kernels up to this long are convolved directly, longer ones by FFT.
+const DIRECT_MAX_TAPS: usize = 64;
+
+// Convolves the signal with a fixed list of taps, e.g. a measured room
+// correction. Short kernels are applied directly, sample by sample. Longer
+// ones use overlap-add FFT convolution in blocks of the kernel length
+// rounded up to a power of two, which costs O(log n) per sample instead of
+// O(n) but delays the output by one block.
+//
+// On top of that comes the kernel's own group delay: (n - 1) / 2 samples for
+// a symmetric, linear-phase kernel of n taps, frequency dependent for others.
+pub struct FirProcessor {
+    convolution: Convolution,
+}
+
+enum Convolution {
+    Direct {
+        taps: Vec<f32>,
+        // Latest inputs, a ring buffer as long as the kernel.
+        history: Vec<f32>,
+        position: usize,
+    },
+    OverlapAdd {
+        fft: Arc<dyn Fft<f32>>,
+        ifft: Arc<dyn Fft<f32>>,
+        // Spectrum of the zero-padded kernel, scaled for the inverse FFT.
+        kernel: Vec<Complex<f32>>,
+        input: Vec<f32>,
+        overlap: Vec<f32>,
+        ready: Vec<f32>,
+        position: usize,
+    },
+}
+
+impl FirProcessor {
+    pub fn new(taps: Vec<f32>) -> Self {
+        if taps.len() <= DIRECT_MAX_TAPS {
+            return FirProcessor {
+                convolution: Convolution::Direct {
+                    history: vec![0.0; taps.len()],
+                    taps,
+                    position: 0,
+                },
+            };
+        }
+        // Twice the block holds a block convolved with the whole kernel.
+        let block = taps.len().next_power_of_two();
+        let fft_len = 2 * block;
+        let mut planner = FftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(fft_len);
+        let mut kernel: Vec<Complex<f32>> = taps
+            .iter()
+            .map(|&t| Complex::new(t / fft_len as f32, 0.0))
+            .collect();
+        kernel.resize(fft_len, Complex::new(0.0, 0.0));
+        fft.process(&mut kernel);
+        FirProcessor {
+            convolution: Convolution::OverlapAdd {
+                fft,
+                ifft: planner.plan_fft_inverse(fft_len),
+                kernel,
+                input: vec![0.0; block],
+                overlap: vec![0.0; fft_len],
+                ready: vec![0.0; block],
+                position: 0,
+            },
+        }
+    }
+}
+
+impl BlockProcessor for FirProcessor {
+    fn process_sample(&mut self, sample: f32) -> Option<f32> {
+        match &mut self.convolution {
+            Convolution::Direct {
+                taps,
+                history,
+                position,
+            } => {
+                history[*position] = sample;
+                // taps[k] weighs the input k samples back.
+                let output = taps
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &tap)| tap * history[(*position + history.len() - k) % history.len()])
+                    .sum();
+                *position = (*position + 1) % history.len();
+                Some(output)
+            }
+            Convolution::OverlapAdd {
+                fft,
+                ifft,
+                kernel,
+                input,
+                overlap,
+                ready,
+                position,
+            } => {
+                let output = ready[*position];
+                input[*position] = sample;
+                *position += 1;
+                if *position == input.len() {
+                    let mut buffer: Vec<Complex<f32>> =
+                        input.iter().map(|&s| Complex::new(s, 0.0)).collect();
+                    buffer.resize(kernel.len(), Complex::new(0.0, 0.0));
+                    fft.process(&mut buffer);
+                    for (bin, k) in buffer.iter_mut().zip(kernel.iter()) {
+                        *bin *= k;
+                    }
+                    ifft.process(&mut buffer);
+                    for (sum, bin) in overlap.iter_mut().zip(&buffer) {
+                        *sum += bin.re;
+                    }
+                    let block = input.len();
+                    ready.copy_from_slice(&overlap[..block]);
+                    overlap.copy_within(block.., 0);
+                    overlap[block..].fill(0.0);
+                    *position = 0;
+                }
+                Some(output)
+            }
+        }
+    }
+}
+
+// Reads taps separated by whitespace or commas; `#` starts a comment.
+pub fn load_taps(path: &str) -> Result<Vec<f32>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read FIR taps {}: {}", path, e))?;
+    let mut taps = Vec::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        for value in line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|v| !v.is_empty())
+        {
+            let tap: f32 = value
+                .parse()
+                .map_err(|_| format!("Invalid FIR tap in {}: {}", path, value))?;
+            if !tap.is_finite() {
+                return Err(format!("Invalid FIR tap in {}: {}", path, value));
+            }
+            taps.push(tap);
+        }
+    }
+    if taps.is_empty() {
+        return Err(format!("{} has no FIR taps", path));
+    }
+    if taps.len() > MAX_TAPS {
+        return Err(format!(
+            "{} has {} FIR taps, at most {} are supported",
+            path,
+            taps.len(),
+            MAX_TAPS
+        ));
+    }
+    Ok(taps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(fir: &mut FirProcessor, input: &[f32]) -> Vec<f32> {
+        input
+            .iter()
+            .map(|&s| fir.process_sample(s).unwrap())
+            .collect()
+    }
+
+    fn assert_close(got: &[f32], want: &[f32]) {
+        assert_eq!(got.len(), want.len());
+        for (n, (g, w)) in got.iter().zip(want).enumerate() {
+            assert!((g - w).abs() < 1e-5, "sample {}: {} != {}", n, g, w);
+        }
+    }
+
+    #[test]
+    fn impulse_reproduces_short_kernel() {
+        let mut fir = FirProcessor::new(vec![0.5, 0.25]);
+        let mut input = vec![0.0; 8];
+        // The second impulse lands after the history has wrapped around.
+        input[0] = 1.0;
+        input[5] = 1.0;
+        assert_close(
+            &run(&mut fir, &input),
+            &[0.5, 0.25, 0.0, 0.0, 0.0, 0.5, 0.25, 0.0],
+        );
+    }
+
+    #[test]
+    fn overlap_add_matches_direct_across_blocks() {
+        // 100 taps are convolved by FFT in blocks of 128.
+        let mut taps = vec![0.0; 100];
+        taps[0] = 0.5;
+        taps[1] = 0.25;
+        taps[99] = -0.125;
+        let block = taps.len().next_power_of_two();
+        let mut fir = FirProcessor::new(taps.clone());
+
+        // Impulses on both sides of a block boundary and one straddling it.
+        let mut input = vec![0.0; 5 * block];
+        input[3] = 1.0;
+        input[block - 1] = 1.0;
+        input[2 * block + 40] = -1.0;
+        let mut expected = vec![0.0; input.len() + 2 * block];
+        for (n, &s) in input.iter().enumerate() {
+            for (k, &tap) in taps.iter().enumerate() {
+                expected[block + n + k] += s * tap;
+            }
+        }
+        input.resize(input.len() + block, 0.0);
+        assert_close(&run(&mut fir, &input), &expected[..input.len()]);
+    }
+}
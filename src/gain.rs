@@ -0,0 +1,15 @@
+use crate::source::BlockProcessor;
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+pub struct GainProcessor {
+    pub gain: f32,
+}
+
+impl BlockProcessor for GainProcessor {
+    fn process_sample(&mut self, input: f32) -> Option<f32> {
+        Some(input * self.gain)
+    }
+}
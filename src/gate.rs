@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+use crate::source::BlockProcessor;
+
+const FFT_SIZE: usize = 2048;
+const HOP_SIZE: usize = FFT_SIZE / 2;
+// Learned thresholds sit this far, about 10 dB, above the average noise
+// magnitude of a bin, which noise seldom reaches.
+const PROFILE_MARGIN: f32 = 3.0;
+// Per-frame factor by which a closing bin's gain falls, so bins that hover
+// around their threshold don't flicker.
+const GAIN_RELEASE: f32 = 0.5;
+
+// Frequency selective noise gate: every FFT bin below its threshold is
+// attenuated by `reduction`, and the signal is rebuilt by overlap-add with
+// square-root Hann windows, which reconstruct the input exactly while no bin
+// is gated. Thresholds are either a fixed floor or learned as the average
+// spectrum of the first frames, which should then hold only the noise.
+//
+// Steady hiss is removed while louder components pass, but noise peaks that
+// briefly cross their threshold survive as short tones, the "musical noise"
+// of spectral denoisers. A larger reduction makes those more obvious; the
+// gain release softens them at the cost of some hiss after loud passages.
+// The output is delayed by `FFT_SIZE` samples.
+pub struct SpectralGate {
+    reduction: f32,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    history: Vec<f32>,
+    overlap: Vec<f32>,
+    ready: Vec<f32>,
+    position: usize,
+    thresholds: Vec<f32>,
+    // Frames averaged into the thresholds, 0 for a fixed floor.
+    learn_frames: usize,
+    learned_frames: usize,
+    gains: Vec<f32>,
+}
+
+impl SpectralGate {
+    // Gates below a fixed level per bin in dBFS, measured like the level of
+    // a tone in that bin.
+    pub fn with_floor(reduction_db: f32, floor_db: f32) -> Self {
+        let mut gate = SpectralGate::new(reduction_db, 0);
+        // A tone of amplitude A peaks at A times half the window sum.
+        let tone_scale = gate.window.iter().sum::<f32>() / 2.0;
+        gate.thresholds
+            .fill(10f32.powf(floor_db / 20.0) * tone_scale);
+        gate
+    }
+
+    // Learns the thresholds from the first `learn_secs` of the input, which
+    // passes unchanged meanwhile.
+    pub fn learning(reduction_db: f32, learn_secs: f32, sample_rate: u32) -> Self {
+        let frames = (learn_secs * sample_rate as f32 / HOP_SIZE as f32).ceil() as usize;
+        SpectralGate::new(reduction_db, frames.max(1))
+    }
+
+    fn new(reduction_db: f32, learn_frames: usize) -> Self {
+        let mut planner = FftPlanner::<f32>::new();
+        SpectralGate {
+            reduction: 10f32.powf(-reduction_db.abs() / 20.0),
+            fft: planner.plan_fft_forward(FFT_SIZE),
+            ifft: planner.plan_fft_inverse(FFT_SIZE),
+            // Periodic, so overlapping squares sum to exactly one.
+            window: (0..FFT_SIZE)
+                .map(|i| (std::f32::consts::PI * i as f32 / FFT_SIZE as f32).sin())
+                .collect(),
+            history: vec![0.0; FFT_SIZE],
+            overlap: vec![0.0; FFT_SIZE],
+            ready: vec![0.0; HOP_SIZE],
+            position: 0,
+            thresholds: vec![0.0; FFT_SIZE],
+            learn_frames,
+            learned_frames: 0,
+            gains: vec![1.0; FFT_SIZE],
+        }
+    }
+
+    fn process_frame(&mut self) {
+        let mut buffer: Vec<Complex<f32>> = self
+            .history
+            .iter()
+            .zip(&self.window)
+            .map(|(&s, &w)| Complex::new(s * w, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+
+        if self.learned_frames < self.learn_frames {
+            for (threshold, bin) in self.thresholds.iter_mut().zip(&buffer) {
+                *threshold += bin.norm() * PROFILE_MARGIN / self.learn_frames as f32;
+            }
+            self.learned_frames += 1;
+        } else {
+            for ((bin, gain), &threshold) in buffer
+                .iter_mut()
+                .zip(self.gains.iter_mut())
+                .zip(&self.thresholds)
+            {
+                let target = if bin.norm() < threshold {
+                    self.reduction
+                } else {
+                    1.0
+                };
+                *gain = target.max(*gain * GAIN_RELEASE);
+                *bin *= *gain;
+            }
+        }
+
+        self.ifft.process(&mut buffer);
+        for ((sum, bin), &w) in self.overlap.iter_mut().zip(&buffer).zip(&self.window) {
+            *sum += bin.re * w / FFT_SIZE as f32;
+        }
+        self.ready.copy_from_slice(&self.overlap[..HOP_SIZE]);
+        self.overlap.copy_within(HOP_SIZE.., 0);
+        self.overlap[FFT_SIZE - HOP_SIZE..].fill(0.0);
+        self.history.copy_within(HOP_SIZE.., 0);
+    }
+}
+
+impl BlockProcessor for SpectralGate {
+    fn process_sample(&mut self, input: f32) -> Option<f32> {
+        let output = self.ready[self.position];
+        self.history[FFT_SIZE - HOP_SIZE + self.position] = input;
+        self.position += 1;
+        if self.position == HOP_SIZE {
+            self.process_frame();
+            self.position = 0;
+        }
+        Some(output)
+    }
+}
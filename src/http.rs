@@ -0,0 +1,297 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::ServerName;
+
+const MAX_REDIRECTS: usize = 5;
+// Longest wait for the server before connecting or reading fails.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Body of the response to an HTTP GET, read from the socket as it arrives,
+// so nothing is downloaded up front. The built-in client speaks HTTP/1.1,
+// over TLS for https:// URLs.
+pub struct HttpStream {
+    reader: BufReader<Connection>,
+    // Content-Type header without parameters, e.g. "audio/mpeg".
+    pub content_type: Option<String>,
+    chunked: bool,
+    // Bytes left in the current chunk of a chunked body.
+    chunk_left: usize,
+    finished: bool,
+}
+
+// The socket of a request, encrypted for https:// URLs.
+enum Connection {
+    Plain(TcpStream),
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+enum Response {
+    Body(HttpStream),
+    Redirect(String),
+}
+
+// Sends a GET request for `url`, following redirects.
+pub fn get(url: &str) -> Result<HttpStream, String> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        match request(&url)? {
+            Response::Body(stream) => return Ok(stream),
+            Response::Redirect(location) => {
+                let target = resolve(&url, &location);
+                // Following it would send the rest of the session in clear.
+                if url.starts_with("https://") && !target.starts_with("https://") {
+                    return Err(format!(
+                        "Refusing the redirect of {} to {}: it leaves https",
+                        url, target
+                    ));
+                }
+                url = target;
+            }
+        }
+    }
+    Err(format!("Too many redirects for {}", url))
+}
+
+fn request(url: &str) -> Result<Response, String> {
+    let (tls, rest) = match (url.strip_prefix("https://"), url.strip_prefix("http://")) {
+        (Some(rest), _) => (true, rest),
+        (_, Some(rest)) => (false, rest),
+        _ => {
+            return Err(format!(
+                "Unsupported URL {}: only http:// and https:// are supported",
+                url
+            ));
+        }
+    };
+    let (authority, path) = rest.find('/').map_or((rest, "/"), |i| rest.split_at(i));
+    let (host, port) = split_authority(authority, if tls { 443 } else { 80 })
+        .ok_or_else(|| format!("Failed to fetch {}: invalid host {}", url, authority))?;
+    let error = |e: io::Error| format!("Failed to fetch {}: {}", url, e);
+
+    let socket = connect(host, port).map_err(error)?;
+    socket.set_read_timeout(Some(TIMEOUT)).map_err(error)?;
+    let mut socket = if tls {
+        let name = ServerName::try_from(host.to_string())
+            .map_err(|_| format!("Failed to fetch {}: invalid host name {}", url, host))?;
+        let connection = rustls::ClientConnection::new(tls_config(), name)
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+        Connection::Tls(Box::new(rustls::StreamOwned::new(connection, socket)))
+    } else {
+        Connection::Plain(socket)
+    };
+    write!(
+        socket,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: sound_heat\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        path, authority
+    )
+    .map_err(error)?;
+
+    let mut reader = BufReader::new(socket);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).map_err(error)?;
+    // "HTTP/1.1 200 OK", or "ICY 200 OK" from Shoutcast servers.
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| format!("Failed to fetch {}: not an HTTP response", url))?;
+
+    let mut location = None;
+    let mut content_type = None;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(error)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "location" => location = Some(value.to_string()),
+            "content-type" => {
+                content_type = value.split(';').next().map(|t| t.trim().to_string());
+            }
+            "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+            _ => {}
+        }
+    }
+
+    match status {
+        200 => Ok(Response::Body(HttpStream {
+            reader,
+            content_type,
+            chunked,
+            chunk_left: 0,
+            finished: false,
+        })),
+        301 | 302 | 303 | 307 | 308 => location
+            .map(Response::Redirect)
+            .ok_or_else(|| format!("Failed to fetch {}: redirect without a location", url)),
+        _ => Err(format!(
+            "Failed to fetch {}: {}",
+            url,
+            status_line.split_once(' ').map_or("", |(_, s)| s).trim()
+        )),
+    }
+}
+
+// Host and port of a URL's authority, e.g. "[::1]:8000" or "example.com".
+// The brackets of an IPv6 address are dropped. `None` for a bad port.
+fn split_authority(authority: &str, default_port: u16) -> Option<(&str, u16)> {
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(rest) => {
+            let (host, after) = rest.split_once(']')?;
+            if after.is_empty() {
+                (host, None)
+            } else {
+                (host, Some(after.strip_prefix(':')?))
+            }
+        }
+        None => match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => default_port,
+    };
+    Some((host, port))
+}
+
+// Tries every address the host resolves to, each within `TIMEOUT`.
+fn connect(host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, TIMEOUT) {
+            Ok(socket) => return Ok(socket),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host has no addresses")))
+}
+
+// Certificates are checked against the Mozilla root store compiled in, so
+// the system's store is neither needed nor consulted.
+fn tls_config() -> Arc<rustls::ClientConfig> {
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    Arc::new(
+        rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )
+}
+
+// Target of a redirect, which may be relative to the URL that sent it.
+fn resolve(url: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let scheme_end = url.find("://").map_or(0, |i| i + 3);
+    let authority_end = url[scheme_end..]
+        .find('/')
+        .map_or(url.len(), |i| i + scheme_end);
+    if location.starts_with('/') {
+        format!("{}{}", &url[..authority_end], location)
+    } else {
+        let base_end = url[authority_end..]
+            .rfind('/')
+            .map_or(url.len(), |i| authority_end + i + 1);
+        let base = &url[..base_end];
+        let separator = if base.ends_with('/') { "" } else { "/" };
+        format!("{}{}{}", base, separator, location)
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(socket) => socket.read(buf),
+            Connection::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(socket) => socket.write(buf),
+            Connection::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(socket) => socket.flush(),
+            Connection::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.chunked {
+            return self.reader.read(buf);
+        }
+        if self.finished {
+            return Ok(0);
+        }
+        if self.chunk_left == 0 {
+            let mut line = String::new();
+            self.reader.read_line(&mut line)?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            self.chunk_left = usize::from_str_radix(size, 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size"))?;
+            if self.chunk_left == 0 {
+                self.finished = true;
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.chunk_left);
+        let read = self.reader.read(&mut buf[..len])?;
+        self.chunk_left -= read;
+        if self.chunk_left == 0 {
+            // Every chunk ends with CRLF.
+            let mut end = [0; 2];
+            self.reader.read_exact(&mut end)?;
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_host_and_port() {
+        assert_eq!(
+            split_authority("example.com", 80),
+            Some(("example.com", 80))
+        );
+        assert_eq!(
+            split_authority("example.com:8000", 80),
+            Some(("example.com", 8000))
+        );
+        assert_eq!(split_authority("[::1]:8000", 80), Some(("::1", 8000)));
+        assert_eq!(
+            split_authority("[2001:db8::1]", 443),
+            Some(("2001:db8::1", 443))
+        );
+        assert_eq!(split_authority("[::1]8000", 80), None);
+        assert_eq!(split_authority("[::1", 80), None);
+        assert_eq!(split_authority("example.com:http", 80), None);
+        assert_eq!(split_authority(":8000", 80), None);
+    }
+}
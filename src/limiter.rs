@@ -0,0 +1,96 @@
+use crate::analysis;
+use crate::dynamics::time_constant;
+
+// True peak ceiling of the normalized export, the usual headroom for lossy
+// encoding on streaming services.
+pub const CEILING_DBTP: f32 = -1.0;
+// How far the gain ramps down ahead of a peak.
+const LOOKAHEAD_MS: f32 = 5.0;
+const RELEASE_MS: f32 = 100.0;
+// Passes over the result before giving up on the last fraction of a dB.
+const MAX_PASSES: usize = 4;
+
+// Brickwall limiter holding the true peak of interleaved samples at or below
+// `ceiling_db`. Works on the whole signal at once, so it can look ahead: the
+// gain every peak needs is reached by a linear ramp over the lookahead before
+// it and recovers exponentially after. All channels share the gain, which
+// keeps the stereo image. Returns the largest gain reduction in dB, zero if
+// the limiter never engaged.
+pub fn limit(samples: &mut [f32], channels: usize, sample_rate: u32, ceiling_db: f32) -> f32 {
+    let frames = samples.len() / channels;
+    let lookahead = ((LOOKAHEAD_MS * 0.001 * sample_rate as f32) as usize).max(1);
+    let release = time_constant(RELEASE_MS, sample_rate);
+    let limit = 10f32.powf(ceiling_db / 20.0);
+    let mut ceiling = limit;
+    let mut total_gain = vec![1.0f32; frames];
+
+    for _ in 0..MAX_PASSES {
+        let peaks = frame_true_peaks(samples, channels);
+        if peaks.iter().all(|&peak| peak <= limit) {
+            break;
+        }
+        let needed: Vec<f32> = peaks
+            .iter()
+            .map(|&peak| if peak > ceiling { ceiling / peak } else { 1.0 })
+            .collect();
+        // The lowest gain needed within the lookahead, averaged over the
+        // lookahead, never exceeds the gain needed at any frame.
+        let ahead = sliding_min(&needed, lookahead);
+        let mut sum: f32 = 0.0;
+        let mut gain = 1.0f32;
+        for n in 0..frames {
+            sum += ahead[n];
+            if n >= lookahead {
+                sum -= ahead[n - lookahead];
+            }
+            let ramp = sum / (n + 1).min(lookahead) as f32;
+            gain = if ramp < gain {
+                ramp
+            } else {
+                release * gain + (1.0 - release) * ramp
+            };
+            for s in &mut samples[n * channels..(n + 1) * channels] {
+                *s *= gain;
+            }
+            total_gain[n] *= gain;
+        }
+        // Gain changes reshape the waveform between samples a little, so the
+        // next pass aims slightly lower.
+        ceiling *= 0.99;
+    }
+    analysis::to_db(total_gain.iter().copied().fold(1.0, f32::min)).abs()
+}
+
+// True peak of every frame over all channels.
+fn frame_true_peaks(samples: &[f32], channels: usize) -> Vec<f32> {
+    let frames = samples.len() / channels;
+    let mut peaks = vec![0.0f32; frames];
+    let mut true_peak = analysis::TruePeak::new();
+    for channel in 0..channels {
+        true_peak.for_each(samples, channels, channel, |n, value| {
+            peaks[n] = peaks[n].max(value)
+        });
+    }
+    peaks
+}
+
+// Minimum of every value and the `width - 1` after it.
+fn sliding_min(values: &[f32], width: usize) -> Vec<f32> {
+    let mut minima = vec![0.0; values.len()];
+    // Indices of increasing values, the front being the current minimum.
+    let mut window = std::collections::VecDeque::new();
+    for n in (0..values.len()).rev() {
+        while window
+            .back()
+            .is_some_and(|&i: &usize| values[i] >= values[n])
+        {
+            window.pop_back();
+        }
+        window.push_back(n);
+        if window.front().is_some_and(|&i| i >= n + width) {
+            window.pop_front();
+        }
+        minima[n] = values[*window.front().unwrap()];
+    }
+    minima
+}
@@ -0,0 +1,141 @@
+use crate::biquad::Biquad;
+
+// Window lengths and gates of ITU-R BS.1770 and EBU R 128.
+const GATING_BLOCK_SECS: f32 = 0.4;
+const GATING_HOP_SECS: f32 = 0.1;
+pub const SHORT_TERM_SECS: f32 = 3.0;
+const ABSOLUTE_GATE: f32 = -70.0;
+const RELATIVE_GATE: f32 = -10.0;
+
+// The two stages of the BS.1770 K filter, a high shelf modelling the head
+// and a high-pass ignoring the lowest frequencies. The analog prototypes are
+// chosen so the bilinear transform gives the standard's 48 kHz coefficients,
+// and the same filter at any other sample rate.
+pub fn k_filter(sample_rate: u32) -> (Biquad, Biquad) {
+    let shelf = {
+        let (freq, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (std::f64::consts::PI * freq / sample_rate as f64).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        Biquad::normalized(
+            [
+                vh + vb * k / q + k * k,
+                2.0 * (k * k - vh),
+                vh - vb * k / q + k * k,
+            ],
+            [
+                1.0 + k / q + k * k,
+                2.0 * (k * k - 1.0),
+                1.0 - k / q + k * k,
+            ],
+        )
+    };
+    let high_pass = {
+        let (freq, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (std::f64::consts::PI * freq / sample_rate as f64).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Biquad::normalized(
+            [a0, -2.0 * a0, a0],
+            [a0, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        )
+    };
+    (shelf, high_pass)
+}
+
+// The K filter of every channel and its BS.1770 weight. The filters keep
+// their state, so a signal can be weighted in pieces as it plays.
+pub struct KWeighting {
+    filters: Vec<(Biquad, Biquad)>,
+    weights: Vec<f32>,
+}
+
+impl KWeighting {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        KWeighting {
+            filters: (0..channels).map(|_| k_filter(sample_rate)).collect(),
+            weights: (0..channels)
+                .map(|channel| channel_weight(channel, channels))
+                .collect(),
+        }
+    }
+
+    // K-weighted power of one interleaved frame, summed over channels.
+    pub fn frame_power(&mut self, frame: &[f32]) -> f32 {
+        frame
+            .iter()
+            .zip(self.filters.iter_mut())
+            .zip(&self.weights)
+            .map(|((&s, (shelf, high_pass)), weight)| {
+                let y = high_pass.process(shelf.process(s));
+                weight * y * y
+            })
+            .sum()
+    }
+}
+
+// K-weighted power of every frame.
+pub fn weighted_power(samples: &[f32], channels: usize, sample_rate: u32) -> Vec<f32> {
+    let mut weighting = KWeighting::new(channels, sample_rate);
+    samples
+        .chunks_exact(channels)
+        .map(|frame| weighting.frame_power(frame))
+        .collect()
+}
+
+// Surround channels of a 5.1 layout count 1.41 times and the LFE not at all.
+fn channel_weight(channel: usize, channels: usize) -> f32 {
+    match (channels, channel) {
+        (6, 3) => 0.0,
+        (6, 4) | (6, 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+pub fn to_lufs(power: f64) -> f32 {
+    (-0.691 + 10.0 * power.log10()) as f32
+}
+
+// Mean power of windows of `window_secs` starting every `hop_secs`.
+fn block_powers(power: &[f32], sample_rate: u32, window_secs: f32, hop_secs: f32) -> Vec<f64> {
+    let window = ((window_secs * sample_rate as f32) as usize).max(1);
+    let hop = ((hop_secs * sample_rate as f32) as usize).max(1);
+    // Prefix sums in f64, so long files don't lose the quiet blocks.
+    let mut sums = Vec::with_capacity(power.len() + 1);
+    sums.push(0.0f64);
+    for &p in power {
+        sums.push(sums[sums.len() - 1] + p as f64);
+    }
+    (0..)
+        .map(|i| i * hop)
+        .take_while(|start| start + window <= power.len())
+        .map(|start| (sums[start + window] - sums[start]) / window as f64)
+        .collect()
+}
+
+// Short-term loudness every `hop_secs`, each value covering the preceding
+// `SHORT_TERM_SECS`.
+pub fn short_term(power: &[f32], sample_rate: u32, hop_secs: f32) -> Vec<f32> {
+    block_powers(power, sample_rate, SHORT_TERM_SECS, hop_secs)
+        .into_iter()
+        .map(to_lufs)
+        .collect()
+}
+
+// Integrated loudness of the whole signal. Blocks below the absolute gate are
+// ignored, then those more than 10 LU below the loudness of the rest, so
+// pauses don't lower the result. `None` if every block is gated.
+pub fn integrated(power: &[f32], sample_rate: u32) -> Option<f32> {
+    let blocks: Vec<f64> = block_powers(power, sample_rate, GATING_BLOCK_SECS, GATING_HOP_SECS)
+        .into_iter()
+        .filter(|&p| to_lufs(p) > ABSOLUTE_GATE)
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+    let threshold = to_lufs(blocks.iter().sum::<f64>() / blocks.len() as f64) + RELATIVE_GATE;
+    let gated: Vec<f64> = blocks
+        .into_iter()
+        .filter(|&p| to_lufs(p) > threshold)
+        .collect();
+    Some(to_lufs(gated.iter().sum::<f64>() / gated.len() as f64))
+}
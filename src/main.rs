@@ -1,6 +1,52 @@
+mod analysis;
+mod automation;
+mod biquad;
+mod chain;
+mod chroma;
+mod cli;
+mod compare;
+#[cfg(feature = "cover-art")]
+mod cover;
+mod cue;
+mod decode;
+mod dither;
+mod dynamics;
+mod expect;
+mod export;
 mod filter;
+mod fir;
+mod gain;
+mod gate;
+#[cfg(feature = "http")]
+mod http;
+mod limiter;
+mod loudness;
+mod meter;
+mod mic;
+mod midi;
+mod npy;
+mod onset;
+#[cfg(feature = "osc")]
+mod osc;
+mod pitch;
+mod plot;
+mod png;
+mod report;
+mod saturator;
+mod scale;
+mod session;
+mod silence;
 mod source;
+mod spectrogram;
 mod spectrum;
+mod stereo;
+mod stream;
+mod target;
+mod tempo;
+mod theme;
+mod tuner;
+mod weighting;
+mod window;
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
@@ -8,71 +54,505 @@ use crossterm::{
     execute,
     terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use rodio::{Decoder as RodioDecoder, Source};
+use rodio::Source;
 use std::collections::VecDeque;
-use std::env;
-use std::fs::File;
-use std::io::{BufReader, stdout};
+use std::io::stdout;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::thread::sleep;
 use std::time::Duration;
 
 const NUM_BANDS: usize = 32;
 const MIN_DB: f32 = -100.0;
 const MAX_DB: f32 = 0.0;
+// Just below 1.0 so the largest positive integer sample counts as full scale.
+const CLIP_LEVEL: f32 = 0.9999;
+// Shorter leading silence is played as it is.
+const MIN_LEAD_SECS: f32 = 0.1;
+// FFT sizes the +/- keys step between.
+const MIN_FFT_SIZE: usize = 256;
+const MAX_FFT_SIZE: usize = 32768;
+// Input a chain runs through before a window rendered on its own.
+const WINDOW_WARMUP_SECS: f32 = 1.0;
 
 fn main() {
-    // Collect command line arguments into a vector of strings.
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        // Print usage if no file path is provided.
-        eprintln!("Usage: {} <file_path>", args[0]);
-        std::process::exit(1);
+    let options = match cli::parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            // Print usage or the parse error if the arguments are invalid.
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    if options.mic {
+        monitor_input(&options);
+        return;
     }
-    let file_path = &args[1];
-    println!("File path provided: {}", file_path);
+    let file_path = &options.file_path;
+    let cue = options
+        .cue
+        .as_ref()
+        .map(|path| match cue::CueSheet::load(path) {
+            Ok(cue) => cue,
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        });
 
-    let source = load_audio(file_path).unwrap();
+    if let Some(expectation) = &options.expect {
+        let result = decode::probe_format(file_path).and_then(|(rate, channels)| {
+            expectation.check(rate, channels).map(|_| (rate, channels))
+        });
+        match result {
+            Ok((rate, channels)) => println!("{}: {} Hz, {} channels", file_path, rate, channels),
+            Err(message) => {
+                eprintln!("{}: {}", file_path, message);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    let sample_rate = source.sample_rate();
-    let channels = source.channels() as usize;
+    // The report decodes on its own so it can skip packets for a fast scan.
+    if options.report {
+        let decoded = match decode::decode_file(file_path, options.fast_scan) {
+            Ok(decoded) => decoded,
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        };
+        let spectrum = build_spectrum(&options, 4096, decoded.sample_rate, decoded.channels);
+        note_mono_side(&options, decoded.channels);
+        report::print_report(
+            file_path,
+            &decoded,
+            &spectrum,
+            cue.as_ref(),
+            options.peak_weighting,
+        );
+        return;
+    }
+
+    // A URL plays while it is decoded, unless the whole track is needed.
+    if decode::is_url(file_path) && !options.needs_whole_track() {
+        stream_url(&options);
+        return;
+    }
+
+    println!("File path provided: {}", file_path);
+
+    let (mut decoded, channels, sample_rate) = match load_samples(file_path) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
     println!("Loaded audio: {} Hz, {} channels", sample_rate, channels);
-    let processors: Vec<Box<dyn source::BlockProcessor + Send>> =
-        vec![Box::new(filter::LowPassFilterBlockProcessor {
-            prev: 0.0,
-            cutoff: 500.0,
+    let mut chain = match build_chain(&options, channels, sample_rate) {
+        Ok(chain) => chain,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    if let Some(tap) = options.tap {
+        if tap > chain.stage_count() {
+            eprintln!(
+                "--tap {}: the chain has {} stages",
+                tap,
+                chain.stage_count()
+            );
+            std::process::exit(1);
+        }
+        chain.tap = tap;
+    }
+
+    if let Some(spec) = &options.automation {
+        match automation::GainEnvelope::parse(spec) {
+            Ok(envelope) => envelope.apply(&mut decoded, channels, sample_rate),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut processed_source =
+        source::ProcessedSource::from_samples(decoded.into(), channels as u16, sample_rate, chain);
+
+    let mut fft_size = 4096;
+    let mut hop_size = fft_size / 2;
+
+    let mut spectrum = build_spectrum(&options, fft_size, sample_rate, channels);
+    apply_theme(&options, &mut spectrum);
+    load_target(&options, &mut spectrum);
+
+    if let Some(export_path) = &options.export {
+        let output_channels = processed_source.channels();
+        let mut ditherer = dither::Ditherer::new(
+            options.bits,
+            output_channels as usize,
+            options.dither_enabled(),
+            options.noise_shaping,
+        );
+        let mut processed: Vec<f32> = processed_source.collect();
+        if let Some(target) = options.normalize_lufs
+            && let Err(message) = normalize_loudness(
+                &mut processed,
+                output_channels as usize,
+                sample_rate,
+                target,
+            )
+        {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        export::write_wav(
+            export_path,
+            &processed,
+            output_channels,
+            sample_rate,
+            options.bits,
+            &mut ditherer,
+        )
+        .unwrap();
+        println!(
+            "Exported {} samples to {} ({}-bit, dither {})",
+            processed.len(),
+            export_path,
+            options.bits,
+            if ditherer.enabled { "on" } else { "off" }
+        );
+        return;
+    }
+
+    if let Some(prefix) = &options.split_on_silence {
+        let output_channels = processed_source.channels() as usize;
+        let processed: Vec<f32> = processed_source.collect();
+        let total_frames = processed.len() / output_channels;
+        let gaps = silence::find_silent_gaps(
+            &processed,
+            output_channels,
+            sample_rate,
+            options.split_threshold_db,
+            options.split_gap_secs,
+        );
+        let segments = silence::segments_between(&gaps, total_frames);
+        write_segments(
+            &options,
+            prefix,
+            &processed,
+            output_channels,
             sample_rate,
-        })];
+            &segments,
+        );
+        println!(
+            "Split into {} segments at {} silent gaps",
+            segments.len(),
+            gaps.len()
+        );
+        return;
+    }
+
+    if let Some(prefix) = &options.split_tracks {
+        let cue = cue.as_ref().unwrap();
+        let output_channels = processed_source.channels() as usize;
+        let processed: Vec<f32> = processed_source.collect();
+        let total_frames = processed.len() / output_channels;
+        let mut ranges = cue.frame_ranges(sample_rate as f32, total_frames);
+        // Tracks the sheet places past the end of the audio have nothing to
+        // write.
+        if let Some(past) = ranges.iter().position(|&(start, _)| start >= total_frames) {
+            for track in &cue.tracks[past..] {
+                eprintln!(
+                    "Warning: track {:02} starts at {}, past the end of the audio at {}; skipped",
+                    track.number,
+                    format_duration_millis(track.start_secs),
+                    format_duration_millis(total_frames as f32 / sample_rate as f32)
+                );
+            }
+            ranges.truncate(past);
+        }
+        write_segments(
+            &options,
+            prefix,
+            &processed,
+            output_channels,
+            sample_rate,
+            &ranges,
+        );
+        println!("Split into {} tracks", ranges.len());
+        return;
+    }
+
+    if let Some(chromagram_path) = &options.chromagram {
+        let chroma = chroma::chromagram(
+            &spectrum,
+            processed_source.get_samples(),
+            hop_size,
+            options.a4,
+        );
+        chroma::write_csv(
+            chromagram_path,
+            &chroma,
+            hop_size as f32 / sample_rate as f32,
+            fft_size as f32 / sample_rate as f32,
+        )
+        .unwrap();
+        println!(
+            "Wrote {} chroma frames to {}",
+            chroma.len(),
+            chromagram_path
+        );
+        return;
+    }
+
+    if let Some(spectrogram_path) = &options.spectrogram {
+        let spectrum = build_spectrum(&options, options.spec_fft, sample_rate, channels);
+        let hop =
+            ((options.spec_fft as f32 * (1.0 - options.spec_overlap)).round() as usize).max(1);
+        let columns = spectrogram::spectrogram(
+            &spectrum,
+            processed_source.get_samples(),
+            hop,
+            options.spec_width,
+            options.spec_height,
+        );
+        let duration =
+            processed_source.get_samples().len() as f32 / channels as f32 / sample_rate as f32;
+        plot::spectrogram_image(
+            spectrogram_path,
+            &columns,
+            duration,
+            (spectrum.min_freq, spectrum.max_freq),
+            options.spec_range,
+        )
+        .unwrap();
+        println!(
+            "Wrote a {} x {} spectrogram to {} (FFT {} {}, hop {})",
+            options.spec_width,
+            options.spec_height,
+            spectrogram_path,
+            options.spec_fft,
+            spectrum.window.name(),
+            hop
+        );
+        return;
+    }
+
+    if let Some(features_path) = &options.features {
+        let matrix = analysis::band_matrix(&spectrum, processed_source.get_samples(), hop_size);
+        npy::write_npy(features_path, &matrix, spectrum.bands).unwrap();
+        println!(
+            "Wrote {} frames x {} bands to {}",
+            matrix.len(),
+            spectrum.bands,
+            features_path
+        );
+        return;
+    }
 
-    let processed_source = source::ProcessedSource::from_source(source, processors);
+    if let Some(graph_path) = &options.loudness_graph {
+        let power = loudness::weighted_power(processed_source.get_samples(), channels, sample_rate);
+        let hop_secs = 0.1;
+        let short_term = loudness::short_term(&power, sample_rate, hop_secs);
+        let integrated = loudness::integrated(&power, sample_rate);
+        let duration = power.len() as f32 / sample_rate as f32;
+        plot::loudness_graph(
+            graph_path,
+            &short_term,
+            loudness::SHORT_TERM_SECS,
+            hop_secs,
+            duration,
+            integrated,
+        )
+        .unwrap();
+        println!(
+            "Wrote {} short-term values to {}, integrated loudness {}",
+            short_term.len(),
+            graph_path,
+            integrated.map_or("below the gate".to_string(), |l| format!("{:.1} LUFS", l))
+        );
+        return;
+    }
+
+    if let Some(midi_path) = &options.midi {
+        let mono = analysis::downmix(
+            processed_source.get_samples(),
+            channels,
+            options.channel_mode,
+        );
+        let track: Vec<Option<i32>> = pitch::pitch_track(&spectrum, &mono, hop_size)
+            .iter()
+            .map(|freq| freq.map(|f| pitch::frequency_to_note(f, options.a4).0))
+            .collect();
+        let notes = midi::notes_from_track(&track, hop_size as f32 / sample_rate as f32);
+        midi::write_midi(midi_path, &notes).unwrap();
+        println!("Wrote {} notes to {}", notes.len(), midi_path);
+        return;
+    }
 
     let total_duration = processed_source
         .total_duration()
         .map_or(0.0, |d| d.as_secs_f32());
-    let samples: Vec<f32> = processed_source.get_samples().clone();
-    println!("Total samples loaded: {}", samples.len());
+    let mut dry = Arc::clone(processed_source.get_samples());
+    println!("Total samples loaded: {}", dry.len());
+    let comparison = load_comparison(&options, &dry, channels, sample_rate);
+    if let Some(comparison) = &comparison {
+        processed_source.play_versions(
+            comparison.versions.clone(),
+            Arc::clone(&comparison.selected),
+        );
+        dry = Arc::clone(&comparison.versions[0]);
+    }
+    let bypass = processed_source.bypass();
+    // The live spectrum analyzes the signal at the tap, after the whole chain
+    // unless chosen otherwise. Playback publishes it as it plays; where the
+    // display needs frames playback hasn't made, as while scrubbing, a second
+    // chain renders just those, see `render_window`.
+    let input_channels = channels;
+    let mut window_chain = match build_chain(&options, channels, sample_rate) {
+        Ok(chain) => chain,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    window_chain.tap = options.tap.unwrap_or(window_chain.stage_count());
+    let channels = window_chain.tap_channels();
+    let feed = (window_chain.tap > 0).then(|| processed_source.publish_tap(2 * MAX_FFT_SIZE));
+    // Every version has the length of the file, so positions at the tap run
+    // up to this.
+    let total_len = dry.len() / input_channels * channels;
+    // The tempo, the leading silence and the silent stretches are found in
+    // the input; the chain doesn't move them.
+    if options.beat_average {
+        match tempo::estimate_beats(&spectrum, &dry) {
+            Some(grid) => {
+                println!("Tempo: {:.1} BPM", grid.bpm());
+                spectrum.beat_grid = Some(grid);
+                spectrum.show_beat_average = true;
+            }
+            None => println!("No steady tempo found, showing the spectrum as usual"),
+        }
+    }
+    spectrum.channels = channels;
+    note_mono_side(&options, channels);
+    // Processed samples for the difference view; chains that change the
+    // channel count can't be compared band by band.
+    let chain_channels = processed_source.channels() as usize;
+
+    if let Err(message) = load_references(&options, &mut spectrum) {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+    let references = &options.references;
+    let mut reference_index = 0;
+    let mut show_reference = !references.is_empty();
+
+    // Playback and analysis start where the content does.
+    let lead = if options.trim_lead {
+        let (start, end) = silence::trim_silence(&dry, input_channels, silence::TRIM_THRESHOLD_DB);
+        let min_lead = (MIN_LEAD_SECS * sample_rate as f32) as usize;
+        if end > start && start >= min_lead {
+            start
+        } else {
+            0
+        }
+    } else {
+        0
+    };
+    let mut pos = lead * channels;
+
+    if options.preroll && total_len >= pos + fft_size * channels {
+        let window = pos..pos + fft_size * channels;
+        spectrum.seed(&render_window(
+            &mut window_chain,
+            &dry,
+            input_channels,
+            sample_rate,
+            window,
+        ));
+    }
+
+    attach_frame_outputs(&options, &mut spectrum);
+
     let stream_handle = rodio::OutputStreamBuilder::open_default_stream().unwrap();
     let mixer = stream_handle.mixer();
     let sink = rodio::Sink::connect_new(mixer);
 
     sink.append(processed_source);
+    if lead > 0
+        && sink
+            .try_seek(Duration::from_secs_f32(lead as f32 / sample_rate as f32))
+            .is_ok()
+    {
+        println!(
+            "Skipped {} of leading silence",
+            format_duration_millis(lead as f32 / sample_rate as f32)
+        );
+    } else {
+        pos = 0;
+    }
 
     println!("Playback started...");
 
-    let fft_size = 4096;
-    let hop_size = fft_size / 2;
-
-    let mut pos = 0;
-
     let mut ring: VecDeque<f32> = VecDeque::with_capacity(fft_size * channels);
 
+    let mut tuner = options
+        .tuner
+        .then(|| tuner::Tuner::new(options.a4, options.tuner_smoothing));
     let mut paused = false;
-    let mut spectrum =
-        spectrum::Spectrum::new(NUM_BANDS, MIN_DB, MAX_DB, 0.8, fft_size, sample_rate);
+    // Latched per channel, so the display says which one overloaded.
+    let mut clipped = vec![false; channels];
+    // Analysis position while paused, in interleaved samples.
+    let mut cursor = pos;
+    let mut scrubbed = false;
+    let skips = if options.skip_silence {
+        silence::skip_ranges(
+            &dry,
+            input_channels,
+            sample_rate,
+            options.skip_threshold_db,
+            options.skip_gap_secs,
+        )
+    } else {
+        Vec::new()
+    };
+    let mut last_skip = None;
+    let mut meter = meter::LevelMeter::new(
+        options.meter_window_ms / 1000.0,
+        spectrum.input_gain,
+        channels,
+        sample_rate,
+    );
+    let mut peak_meter = options.peak_weighting.map(|weighting| {
+        meter::PeakMeter::new(weighting, spectrum.input_gain, channels, sample_rate)
+    });
+    let mut wet: Option<Vec<f32>> = None;
+    let mut bypassed = false;
+    let mut show_difference = false;
 
-    enable_raw_mode().unwrap();
-    execute!(stdout(), EnterAlternateScreen).unwrap();
+    if !options.print_frames {
+        enable_raw_mode().unwrap();
+        execute!(
+            stdout(),
+            EnterAlternateScreen,
+            crossterm::style::SetBackgroundColor(spectrum.theme.background),
+            crossterm::style::SetForegroundColor(spectrum.theme.text),
+            Clear(ClearType::All)
+        )
+        .unwrap();
+        spectrum.top_row = draw_cover_art(file_path);
+    }
+    let top = spectrum.top_row;
 
-    while pos < samples.len() {
+    // The latest hop of what the display analyzes.
+    let mut chunk = Vec::new();
+    while pos < total_len {
         if event::poll(Duration::from_millis(10)).unwrap() {
             match event::read().unwrap() {
                 Event::Key(KeyEvent {
@@ -84,6 +564,184 @@ fn main() {
                         sink.pause();
                     } else {
                         sink.play();
+                        // Back from where the window was scrubbed to.
+                        if cursor != pos {
+                            spectrum.reset_hold();
+                        }
+                    }
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('r'),
+                    ..
+                }) => {
+                    show_reference = !show_reference && !references.is_empty();
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('n'),
+                    ..
+                }) if !references.is_empty() => {
+                    reference_index = (reference_index + 1) % references.len();
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    ..
+                }) => {
+                    clipped.fill(false);
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('e'),
+                    ..
+                }) => {
+                    spectrum.show_energy_share = !spectrum.show_energy_share;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('o'),
+                    ..
+                }) => {
+                    spectrum.color_by_octave = !spectrum.color_by_octave;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('b'),
+                    ..
+                }) => {
+                    spectrum.show_beat_average =
+                        !spectrum.show_beat_average && spectrum.beat_grid.is_some();
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('v'),
+                    ..
+                }) => {
+                    spectrum.draw_curve = !spectrum.draw_curve;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('f'),
+                    ..
+                }) => {
+                    spectrum.floor_shading = !spectrum.floor_shading;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('s'),
+                    ..
+                }) => {
+                    spectrum.take_snapshot();
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('x'),
+                    ..
+                }) => {
+                    spectrum.snapshot = None;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('t'),
+                    ..
+                }) => {
+                    spectrum.show_target = !spectrum.show_target && spectrum.target.is_some();
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('d'),
+                    ..
+                }) if chain_channels == input_channels && channels == input_channels => {
+                    // The processed signal is only rendered the first time.
+                    if wet.is_none() {
+                        wet = Some(render_wet(&options, &dry, channels, sample_rate));
+                    }
+                    show_difference = !show_difference;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('p'),
+                    ..
+                }) if chain_channels == input_channels && channels == input_channels => {
+                    bypassed = !bypassed;
+                    bypass.store(bypassed, Ordering::Relaxed);
+                    // The display follows what is heard.
+                    let window = pos.saturating_sub(fft_size * channels)..pos;
+                    read_display(feed.as_deref(), bypassed, &dry, window, &mut chunk);
+                    ring.clear();
+                    ring.extend(&chunk);
+                    scrubbed = paused;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('a'),
+                    ..
+                }) if comparison.is_some() => {
+                    let index = comparison.as_ref().unwrap().next();
+                    dry = Arc::clone(&comparison.as_ref().unwrap().versions[index]);
+                    if wet.is_some() {
+                        wet = Some(render_wet(&options, &dry, channels, sample_rate));
+                    }
+                    // So the next frame is all of the new file: refilled from
+                    // the input, or emptied where playback only now starts to
+                    // publish the new file's tap.
+                    ring.clear();
+                    if feed.is_none() || bypassed {
+                        ring.extend(&dry[pos.saturating_sub(fft_size * channels)..pos]);
+                    }
+                    scrubbed = paused;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(key @ ('+' | '=' | '-')),
+                    ..
+                }) => {
+                    fft_size = step_fft_size(&mut spectrum, key != '-');
+                    hop_size = fft_size / 2;
+                    // Refilled, so the next frame has the new length.
+                    let window = pos.saturating_sub(fft_size * channels)..pos;
+                    read_display(feed.as_deref(), bypassed, &dry, window, &mut chunk);
+                    ring.clear();
+                    ring.extend(&chunk);
+                    scrubbed = paused;
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Left,
+                    ..
+                }) if paused => {
+                    cursor = cursor.saturating_sub(hop_size * channels);
+                    scrubbed = true;
+                    spectrum.reset_hold();
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Right,
+                    ..
+                }) if paused => {
+                    cursor = (cursor + hop_size * channels).min(total_len);
+                    scrubbed = true;
+                    spectrum.reset_hold();
+                }
+
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(key @ ('[' | ']')),
+                    ..
+                }) if cue.is_some() => {
+                    let cue = cue.as_ref().unwrap();
+                    let current = cue.track_at(pos as f32 / channels as f32 / sample_rate as f32);
+                    let target = if key == ']' {
+                        (current + 1).min(cue.tracks.len() - 1)
+                    } else {
+                        current.saturating_sub(1)
+                    };
+                    let start = cue.tracks[target].start_secs;
+                    if sink.try_seek(Duration::from_secs_f32(start)).is_ok() {
+                        pos = ((start * sample_rate as f32) as usize * channels).min(total_len);
+                        cursor = pos;
+                        ring.clear();
+                        spectrum.reset_hold();
                     }
                 }
 
@@ -98,61 +756,974 @@ fn main() {
             }
         }
 
+        // While paused the analysis window can be moved independently of
+        // playback; otherwise it follows the samples being played.
+        let mut frame = None;
         if paused {
-            sleep(Duration::from_millis(10));
-            continue;
-        }
+            let window = fft_size * channels;
+            if std::mem::take(&mut scrubbed) && total_len >= window {
+                let end = cursor.clamp(window, total_len);
+                let timestamp = end as f32 / channels as f32 / sample_rate as f32;
+                let samples = if bypassed {
+                    dry[end - window..end].to_vec()
+                } else {
+                    let range = end - window..end;
+                    render_window(&mut window_chain, &dry, input_channels, sample_rate, range)
+                };
+                frame = Some((samples, end, timestamp, true));
+            } else {
+                sleep(Duration::from_millis(10));
+                continue;
+            }
+        } else {
+            let frame_index = pos / channels;
+            // Searched every hop, since track keys can jump backwards.
+            let next_skip = skips.partition_point(|&(_, end)| end <= frame_index);
+            if let Some(&(start, end)) = skips.get(next_skip)
+                && frame_index >= start
+                && sink
+                    .try_seek(Duration::from_secs_f32(end as f32 / sample_rate as f32))
+                    .is_ok()
+            {
+                let message = format!(
+                    "Skipped {} of silence at {}",
+                    format_duration_millis((end - frame_index) as f32 / sample_rate as f32),
+                    format_duration(frame_index as f32 / sample_rate as f32)
+                );
+                if options.print_frames {
+                    println!("{}", message);
+                }
+                last_skip = Some(message);
+                pos = end * channels;
+                spectrum.reset_hold();
+            }
+            let end = (pos + hop_size * channels).min(total_len);
+            // Playback can be a little behind, e.g. right after a seek.
+            if !read_display(feed.as_deref(), bypassed, &dry, pos..end, &mut chunk) {
+                continue;
+            }
+            meter.update(&chunk);
+            if let Some(peak_meter) = &mut peak_meter {
+                peak_meter.update(&chunk);
+            }
+            // Latch until cleared so short overloads aren't missed.
+            for (channel, latched) in clipped.iter_mut().enumerate() {
+                *latched |=
+                    analysis::peak(analysis::channel(&chunk, channels, channel)) >= CLIP_LEVEL;
+            }
 
-        let end = (pos + hop_size * channels).min(samples.len());
-        let chunk = &samples[pos..end];
+            for &s in &chunk {
+                if ring.len() == fft_size * channels {
+                    ring.pop_front();
+                }
+                ring.push_back(s);
+            }
+            pos = end;
+            cursor = pos;
 
-        for &s in chunk {
             if ring.len() == fft_size * channels {
-                ring.pop_front();
+                let timestamp = pos as f32 / channels as f32 / sample_rate as f32;
+                frame = Some((ring.make_contiguous().to_vec(), pos, timestamp, false));
             }
-            ring.push_back(s);
         }
-        pos = end;
 
-        if ring.len() == fft_size * channels {
-            let mut frame: Vec<f32> = Vec::with_capacity(fft_size);
-            for i in 0..fft_size {
-                let mut sum = 0.0;
-                for ch in 0..channels {
-                    sum += ring[i * channels + ch];
+        // Raw levels of a frame whose bars are still moving towards it.
+        let mut interpolating = None;
+        if let Some((frame, end, timestamp, scrubbing)) = frame {
+            if options.print_frames {
+                spectrum.analyze(&frame, timestamp);
+                sleep(Duration::from_secs_f32(
+                    hop_size as f32 / sample_rate as f32,
+                ));
+                continue;
+            }
+            // Clear below the cover art only, so it isn't sent again every frame.
+            execute!(
+                stdout(),
+                crossterm::cursor::MoveTo(0, top),
+                Clear(ClearType::FromCursorDown)
+            )
+            .unwrap();
+
+            let position = if scrubbing {
+                format!(
+                    "Cursor: {} / {} (left/right to move, space to resume)",
+                    format_duration_millis(timestamp),
+                    format_duration(total_duration)
+                )
+            } else {
+                format!(
+                    "Current position: {} / {} | {} | {}",
+                    format_duration(sink.get_pos().as_secs_f32()),
+                    format_duration(total_duration),
+                    meter_status(&meter, peak_meter.as_ref()),
+                    fft_status(&spectrum)
+                )
+            };
+            let position = match &cue {
+                Some(cue) => {
+                    let track = &cue.tracks[cue.track_at(timestamp)];
+                    format!("{} | Track {}", position, track.label())
+                }
+                None => position,
+            };
+            execute!(
+                stdout(),
+                crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 2),
+                crossterm::style::Print(position),
+            )
+            .unwrap();
+            draw_clip(&clipped, top, &spectrum.theme);
+            if bypassed {
+                execute!(
+                    stdout(),
+                    crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 10),
+                    crossterm::style::SetForegroundColor(spectrum.theme.high),
+                    crossterm::style::Print("BYPASS"),
+                    crossterm::style::SetForegroundColor(spectrum.theme.text),
+                    crossterm::style::Print(" the chain is off (p to turn it on)"),
+                )
+                .unwrap();
+            }
+            if show_reference {
+                spectrum.overlay = Some(reference_index);
+                execute!(
+                    stdout(),
+                    crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 3),
+                    crossterm::style::Print(format!("Reference: {}", references[reference_index])),
+                )
+                .unwrap();
+            } else {
+                spectrum.overlay = None;
+            }
+            // The tuner and the difference view take the place of the bars.
+            if tuner.is_none() && !(show_difference && wet.is_some()) {
+                draw_view_label(options.target.as_deref(), &spectrum, top);
+            }
+            if let Some(grid) = &spectrum.beat_grid
+                && spectrum.show_beat_average
+            {
+                execute!(
+                    stdout(),
+                    crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 8),
+                    crossterm::style::Print(format!(
+                        "Beat average at {:.1} BPM (b to toggle)",
+                        grid.bpm()
+                    )),
+                )
+                .unwrap();
+            }
+            if let Some(comparison) = &comparison {
+                execute!(
+                    stdout(),
+                    crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 9),
+                    crossterm::style::Print(format!("{} (a to switch)", comparison.status())),
+                )
+                .unwrap();
+            }
+            if let Some(message) = &last_skip {
+                execute!(
+                    stdout(),
+                    crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 5),
+                    crossterm::style::Print(message),
+                )
+                .unwrap();
+            }
+            if options.show_delay {
+                let delay = if analysis::is_mono(channels) {
+                    analysis::MONO_NOTE.to_string()
+                } else {
+                    analysis::channel_delay_samples(&frame, channels, sample_rate)
+                        .map_or("-".to_string(), |(lag, millis)| {
+                            format!("{} samples ({:.2} ms)", lag, millis)
+                        })
+                };
+                execute!(
+                    stdout(),
+                    crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 4),
+                    crossterm::style::Print(format!("Delay R-L: {}", delay)),
+                )
+                .unwrap();
+            }
+            if let Some(tuner) = &mut tuner {
+                // Analyzed without drawing the bars, so the frame callbacks
+                // (--print-frames, --log, --osc) still get every hop.
+                if !scrubbing {
+                    spectrum.analyze(&frame, timestamp);
+                }
+                let mono = analysis::downmix(&frame, channels, options.channel_mode);
+                let silent = analysis::to_db(analysis::rms(&mono)) < pitch::SILENCE_DB;
+                let frequency = if silent {
+                    None
+                } else {
+                    pitch::hps_pitch(&spectrum.magnitudes(&mono), sample_rate, spectrum.fft_len())
+                };
+                tuner.update(frequency);
+                tuner.draw(top, &spectrum.theme, &mut stdout());
+            } else if show_difference && let Some(wet) = &wet {
+                // Analyzed as usual too, so the bars and frame callbacks
+                // carry on while the difference is shown.
+                if !scrubbing {
+                    spectrum.analyze(&frame, timestamp);
+                }
+                // All three signals have the same layout, see the 'd' key.
+                let range = end.saturating_sub(frame.len())..end.min(wet.len());
+                let difference: Vec<f32> = spectrum
+                    .band_levels(&wet[range.clone()])
+                    .iter()
+                    .zip(spectrum.band_levels(&dry[range]))
+                    .map(|(&wet, dry)| wet.max(spectrum.min_db) - dry.max(spectrum.min_db))
+                    .collect();
+                spectrum.draw_difference(&difference, &mut stdout());
+                execute!(
+                    stdout(),
+                    crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 6),
+                    crossterm::style::Print("Difference: processed minus dry (d to toggle)"),
+                )
+                .unwrap();
+            } else if scrubbing {
+                // Show the exact window under the cursor, without smoothing.
+                let levels = spectrum.seed(&frame);
+                spectrum.draw(&levels, &mut stdout());
+            } else if options.interpolate {
+                let levels = spectrum.analyze(&frame, timestamp);
+                spectrum.draw_interpolated(&levels, 0.0, &mut stdout());
+                interpolating = Some(levels);
+            } else {
+                spectrum.render(&frame, timestamp, &mut stdout());
+            }
+        }
+
+        if !paused {
+            let hop = Duration::from_secs_f32(hop_size as f32 / sample_rate as f32);
+            match interpolating {
+                // Spread the change over the hop, in steps of one display frame.
+                Some(levels) => {
+                    let steps = (options.fps * hop.as_secs_f32()).round().max(1.0) as u32;
+                    for step in 1..=steps {
+                        sleep(hop / steps);
+                        let fraction = step as f32 / steps as f32;
+                        spectrum.draw_interpolated(&levels, fraction, &mut stdout());
+                    }
+                }
+                None => sleep(hop),
+            }
+        }
+    }
+    if !options.print_frames {
+        execute!(stdout(), crossterm::style::ResetColor, LeaveAlternateScreen).unwrap();
+        disable_raw_mode().unwrap();
+    }
+    sink.sleep_until_end();
+}
+
+// Live display of an input device instead of a file. Nothing is played and
+// the chain isn't applied, so the bars show what the device picks up.
+fn monitor_input(options: &cli::Options) {
+    let input = match mic::MicInput::open(options.input_device.as_deref()) {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "Recording from {}: {} Hz, {} channels",
+        input.name, input.sample_rate, input.channels
+    );
+    monitor_live(options, &input);
+}
+
+// Plays a URL while decoding it, so streams that never end can be shown.
+fn stream_url(options: &cli::Options) {
+    let input = decode::StreamDecoder::open(&options.file_path).and_then(|decoder| {
+        let chain = build_chain(options, decoder.channels, decoder.sample_rate)?;
+        stream::StreamInput::play(&options.file_path, decoder, chain)
+    });
+    let input = match input {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "Streaming {}: {} Hz, {} channels",
+        input.name, input.sample_rate, input.channels
+    );
+    monitor_live(options, &input);
+}
+
+// The live display of audio arriving from `input`, without seeking or
+// anything else that needs the whole signal in advance.
+fn monitor_live(options: &cli::Options, input: &dyn mic::LiveInput) {
+    let sample_rate = input.sample_rate();
+    let channels = input.channels();
+
+    let mut fft_size = 4096;
+    let mut hop_size = fft_size / 2;
+    let mut spectrum = build_spectrum(options, fft_size, sample_rate, channels);
+    note_mono_side(options, channels);
+    apply_theme(options, &mut spectrum);
+    load_target(options, &mut spectrum);
+    if let Err(message) = load_references(options, &mut spectrum) {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+    let references = &options.references;
+    let mut reference_index = 0;
+    let mut show_reference = !references.is_empty();
+    attach_frame_outputs(options, &mut spectrum);
+    let mut tuner = options
+        .tuner
+        .then(|| tuner::Tuner::new(options.a4, options.tuner_smoothing));
+
+    if !options.print_frames {
+        enable_raw_mode().unwrap();
+        execute!(
+            stdout(),
+            EnterAlternateScreen,
+            crossterm::style::SetBackgroundColor(spectrum.theme.background),
+            crossterm::style::SetForegroundColor(spectrum.theme.text),
+            Clear(ClearType::All)
+        )
+        .unwrap();
+    }
+
+    let mut ring: VecDeque<f32> = VecDeque::with_capacity(fft_size * channels);
+    let mut clipped = vec![false; channels];
+    let mut paused = false;
+    let mut meter = meter::LevelMeter::new(
+        options.meter_window_ms / 1000.0,
+        spectrum.input_gain,
+        channels,
+        sample_rate,
+    );
+    let mut peak_meter = options.peak_weighting.map(|weighting| {
+        meter::PeakMeter::new(weighting, spectrum.input_gain, channels, sample_rate)
+    });
+    // Interleaved samples recorded in total and since the last analysis.
+    let mut recorded = 0;
+    let mut pending = 0;
+    let result = loop {
+        if event::poll(Duration::from_millis(10)).unwrap()
+            && let Event::Key(KeyEvent { code, .. }) = event::read().unwrap()
+        {
+            match code {
+                KeyCode::Char(' ') => {
+                    paused = !paused;
+                    input.set_paused(paused);
+                }
+                KeyCode::Char('r') => show_reference = !show_reference && !references.is_empty(),
+                KeyCode::Char('n') if !references.is_empty() => {
+                    reference_index = (reference_index + 1) % references.len();
                 }
-                frame.push(sum / channels as f32);
+                KeyCode::Char('c') => clipped.fill(false),
+                KeyCode::Char('e') => spectrum.show_energy_share = !spectrum.show_energy_share,
+                KeyCode::Char('o') => spectrum.color_by_octave = !spectrum.color_by_octave,
+                KeyCode::Char('v') => spectrum.draw_curve = !spectrum.draw_curve,
+                KeyCode::Char('f') => spectrum.floor_shading = !spectrum.floor_shading,
+                KeyCode::Char('s') => spectrum.take_snapshot(),
+                KeyCode::Char('x') => spectrum.snapshot = None,
+                KeyCode::Char(key @ ('+' | '=' | '-')) => {
+                    fft_size = step_fft_size(&mut spectrum, key != '-');
+                    hop_size = fft_size / 2;
+                    // A larger window fills up from new recordings.
+                    while ring.len() > fft_size * channels {
+                        ring.pop_front();
+                    }
+                }
+                KeyCode::Char('t') => {
+                    spectrum.show_target = !spectrum.show_target && spectrum.target.is_some();
+                }
+                KeyCode::Char('q') => break Ok(()),
+                _ => {}
+            }
+        }
+
+        // Recorded audio is still collected while paused, but thrown away.
+        let finished = input.finished();
+        let chunk = match input.receive() {
+            Ok(chunk) => chunk,
+            Err(message) => break Err(message),
+        };
+        if finished && chunk.is_empty() {
+            break Ok(());
+        }
+        if paused {
+            continue;
+        }
+        for (channel, latched) in clipped.iter_mut().enumerate() {
+            *latched |= analysis::peak(analysis::channel(&chunk, channels, channel)) >= CLIP_LEVEL;
+        }
+        meter.update(&chunk);
+        if let Some(peak_meter) = &mut peak_meter {
+            peak_meter.update(&chunk);
+        }
+        for &s in &chunk {
+            if ring.len() == fft_size * channels {
+                ring.pop_front();
             }
-            execute!(stdout(), Clear(ClearType::All)).unwrap();
+            ring.push_back(s);
+        }
+        recorded += chunk.len();
+        pending += chunk.len();
+        // One frame per hop; if drawing fell behind, only the latest counts.
+        if pending < hop_size * channels || ring.len() < fft_size * channels {
+            continue;
+        }
+        pending = 0;
 
+        let frame = ring.make_contiguous().to_vec();
+        let timestamp = recorded as f32 / channels as f32 / sample_rate as f32;
+        if options.print_frames {
+            spectrum.analyze(&frame, timestamp);
+            continue;
+        }
+        execute!(
+            stdout(),
+            crossterm::cursor::MoveTo(0, 0),
+            Clear(ClearType::FromCursorDown),
+            crossterm::cursor::MoveTo(0, NUM_BANDS as u16 + 2),
+            crossterm::style::Print(format!(
+                "{}: {} | {} | {}",
+                input.label(),
+                format_duration(timestamp),
+                meter_status(&meter, peak_meter.as_ref()),
+                fft_status(&spectrum)
+            )),
+        )
+        .unwrap();
+        draw_clip(&clipped, 0, &spectrum.theme);
+        if tuner.is_none() {
+            draw_view_label(options.target.as_deref(), &spectrum, 0);
+        }
+        if show_reference {
+            spectrum.overlay = Some(reference_index);
             execute!(
                 stdout(),
-                crossterm::cursor::MoveTo(0, NUM_BANDS as u16 + 2),
-                crossterm::style::Print(format!(
-                    "Current position: {} / {}",
-                    format_duration(sink.get_pos().as_secs_f32()),
-                    format_duration(total_duration)
-                )),
+                crossterm::cursor::MoveTo(0, NUM_BANDS as u16 + 3),
+                crossterm::style::Print(format!("Reference: {}", references[reference_index])),
             )
             .unwrap();
-            spectrum.render(&frame, &mut stdout());
+        } else {
+            spectrum.overlay = None;
         }
+        if let Some(tuner) = &mut tuner {
+            // Analyzed without drawing the bars, for the frame callbacks.
+            spectrum.analyze(&frame, timestamp);
+            let mono = analysis::downmix(&frame, channels, options.channel_mode);
+            let silent = analysis::to_db(analysis::rms(&mono)) < pitch::SILENCE_DB;
+            let frequency = if silent {
+                None
+            } else {
+                pitch::hps_pitch(&spectrum.magnitudes(&mono), sample_rate, spectrum.fft_len())
+            };
+            tuner.update(frequency);
+            tuner.draw(0, &spectrum.theme, &mut stdout());
+        } else {
+            spectrum.render(&frame, timestamp, &mut stdout());
+        }
+    };
+    if !options.print_frames {
+        execute!(stdout(), crossterm::style::ResetColor, LeaveAlternateScreen).unwrap();
+        disable_raw_mode().unwrap();
+    }
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
 
-        sleep(Duration::from_secs_f32(
-            hop_size as f32 / sample_rate as f32,
+// Side mode analyzes L - R, which is silent for mono; say so rather than
+// leave the display empty without a reason.
+fn note_mono_side(options: &cli::Options, channels: usize) {
+    if analysis::is_mono(channels) && options.channel_mode == analysis::ChannelMode::Side {
+        println!("Note: the input is mono, so its side signal is silent");
+    }
+}
+
+// Halves or doubles the FFT size, within `MIN_FFT_SIZE` and `MAX_FFT_SIZE`,
+// and returns the new size.
+fn step_fft_size(spectrum: &mut spectrum::Spectrum, larger: bool) -> usize {
+    let size = if larger {
+        spectrum.fft_size * 2
+    } else {
+        spectrum.fft_size / 2
+    };
+    spectrum.set_fft_size(size.clamp(MIN_FFT_SIZE, MAX_FFT_SIZE));
+    spectrum.fft_size
+}
+
+// The level readouts of the status line.
+fn meter_status(meter: &meter::LevelMeter, peak_meter: Option<&meter::PeakMeter>) -> String {
+    match peak_meter {
+        Some(peak_meter) => format!("{} | {}", meter.status(), peak_meter.status()),
+        None => meter.status(),
+    }
+}
+
+fn fft_status(spectrum: &spectrum::Spectrum) -> String {
+    format!(
+        "FFT {} {} ({:.1} Hz bins)",
+        spectrum.fft_size,
+        spectrum.window.name(),
+        spectrum.sample_rate as f32 / spectrum.fft_len() as f32
+    )
+}
+
+fn build_spectrum(
+    options: &cli::Options,
+    fft_size: usize,
+    sample_rate: u32,
+    channels: usize,
+) -> spectrum::Spectrum {
+    let mut spectrum =
+        spectrum::Spectrum::new(NUM_BANDS, MIN_DB, MAX_DB, 0.8, fft_size, sample_rate);
+    spectrum.channels = channels;
+    spectrum.channel_mode = options.channel_mode;
+    spectrum.set_zero_pad_factor(options.zero_pad);
+    if let Some(max_freq) = options.max_freq {
+        // Allow ultrasonic content to be shown, but never beyond Nyquist.
+        spectrum.max_freq = max_freq.clamp(spectrum.min_freq + 1.0, sample_rate as f32 / 2.0);
+    }
+    if let Some(silence_db) = options.silence_db {
+        spectrum.silence_db = silence_db;
+    }
+    spectrum.input_gain = 10f32.powf(options.input_gain_db / 20.0);
+    spectrum.window = options.window;
+    spectrum.enbw_correction = options.enbw_correction;
+    spectrum.include_dc = options.include_dc;
+    spectrum.bar_scale = options.bar_scale;
+    spectrum.hold_secs = options.hold_secs;
+    spectrum.show_peaks = options.band_peaks;
+    spectrum.show_energy_share = options.energy_share;
+    spectrum.color_by_octave = options.octave_colors;
+    spectrum.draw_curve = options.curve;
+    spectrum.floor_shading = options.floor_shading;
+    if options.band_smoothing {
+        spectrum.use_frequency_dependent_smoothing();
+    }
+    spectrum
+}
+
+fn apply_theme(options: &cli::Options, spectrum: &mut spectrum::Spectrum) {
+    if let Some(name) = &options.theme {
+        match theme::Theme::load(name) {
+            Ok(theme) => spectrum.theme = theme,
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// Names what is drawn in place of the levels: the change since a snapshot,
+// or the deviation from the target curve.
+fn draw_view_label(path: Option<&str>, spectrum: &spectrum::Spectrum, top: u16) {
+    if spectrum.snapshot.is_some() {
+        execute!(
+            stdout(),
+            crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 7),
+            crossterm::style::Print(
+                "Snapshot: change of every band since (s to retake, x to clear)"
+            ),
+        )
+        .unwrap();
+    } else if let Some(path) = path
+        && spectrum.show_target
+    {
+        execute!(
+            stdout(),
+            crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 7),
+            crossterm::style::Print(format!(
+                "Target: {}, average spectrum minus target (t to toggle)",
+                path
+            )),
+        )
+        .unwrap();
+    }
+}
+
+// Largest gain --normalize-lufs applies; quieter material is mostly noise
+// or a mistake, and boosting it further would only raise the noise floor.
+const MAX_NORMALIZE_BOOST_DB: f32 = 20.0;
+
+// Gains the samples to `target` LUFS integrated, with the limiter keeping
+// the true peak below its ceiling, and reports what was done.
+fn normalize_loudness(
+    samples: &mut [f32],
+    channels: usize,
+    sample_rate: u32,
+    target: f32,
+) -> Result<(), String> {
+    let power = loudness::weighted_power(samples, channels, sample_rate);
+    let measured = loudness::integrated(&power, sample_rate)
+        .ok_or("--normalize-lufs: the audio is too short or silent to measure")?;
+    let gain_db = target - measured;
+    if gain_db > MAX_NORMALIZE_BOOST_DB {
+        return Err(format!(
+            "--normalize-lufs: reaching {:.1} LUFS from {:.1} LUFS needs {:+.1} dB of gain, \
+             more than the {:.0} dB allowed",
+            target, measured, gain_db, MAX_NORMALIZE_BOOST_DB
         ));
     }
-    execute!(stdout(), LeaveAlternateScreen).unwrap();
-    disable_raw_mode().unwrap();
-    sink.sleep_until_end();
+    let gain = 10f32.powf(gain_db / 20.0);
+    for s in samples.iter_mut() {
+        *s *= gain;
+    }
+    let reduction_db = limiter::limit(samples, channels, sample_rate, limiter::CEILING_DBTP);
+    println!(
+        "Normalized {:.1} LUFS to {:.1} LUFS with {:+.1} dB of gain",
+        measured, target, gain_db
+    );
+    if reduction_db > 0.0 {
+        let power = loudness::weighted_power(samples, channels, sample_rate);
+        let limited = loudness::integrated(&power, sample_rate).unwrap_or(target);
+        println!(
+            "Limiter engaged: up to {:.1} dB of reduction to stay under {:.1} dBTP, {:.1} LUFS after limiting",
+            reduction_db,
+            limiter::CEILING_DBTP,
+            limited
+        );
+    } else {
+        println!(
+            "Limiter not engaged, true peak under {:.1} dBTP",
+            limiter::CEILING_DBTP
+        );
+    }
+    Ok(())
+}
+
+fn load_target(options: &cli::Options, spectrum: &mut spectrum::Spectrum) {
+    let Some(path) = &options.target else {
+        return;
+    };
+    let curve = match target::TargetCurve::load(path) {
+        Ok(curve) => curve,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    // Compared at the centre of every band.
+    let levels = (0..spectrum.bands)
+        .map(|band| {
+            let (low_freq, high_freq) = spectrum.band_range(band);
+            curve.level_at((low_freq * high_freq).sqrt())
+        })
+        .collect();
+    spectrum.target = Some(levels);
+    spectrum.show_target = true;
+}
+
+// Registers the per-frame outputs chosen on the command line: printed
+// frames, the session log and OSC.
+fn attach_frame_outputs(options: &cli::Options, spectrum: &mut spectrum::Spectrum) {
+    if options.print_frames {
+        spectrum.on_frame(|info| {
+            let bands: Vec<String> = info
+                .band_levels
+                .iter()
+                .map(|db| format!("{:.1}", db))
+                .collect();
+            println!(
+                "t={:.3} rms={:.1} peak={} onset={} bands={}",
+                info.timestamp,
+                info.rms_db,
+                info.peak_frequency
+                    .map_or("-".to_string(), |f| format!("{:.1}", f)),
+                info.onset as u8,
+                bands.join(",")
+            );
+        });
+    }
+
+    if let Some(path) = &options.log {
+        let mut log = match session::SessionLog::new(path, options.log_interval, CLIP_LEVEL) {
+            Ok(log) => log,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        spectrum.on_frame(move |info| log.record(info));
+    }
+
+    #[cfg(feature = "osc")]
+    if let Some(target) = &options.osc_target {
+        let sender = match osc::OscSender::new(
+            target,
+            &options.osc_bands_address,
+            &options.osc_beat_address,
+        ) {
+            Ok(sender) => sender,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        spectrum.on_frame(move |info| sender.send(info));
+    }
+}
+
+// Latched clip indicator, naming the channels that overloaded.
+fn draw_clip(clipped: &[bool], top: u16, theme: &theme::Theme) {
+    if !clipped.contains(&true) {
+        return;
+    }
+    let names: Vec<String> = (0..clipped.len())
+        .filter(|&channel| clipped[channel])
+        .map(|channel| analysis::channel_name(channel, clipped.len()))
+        .collect();
+    execute!(
+        stdout(),
+        crossterm::cursor::MoveTo(0, top + NUM_BANDS as u16 + 1),
+        crossterm::style::SetForegroundColor(theme.high),
+        crossterm::style::Print(format!("CLIP {}", names.join(" "))),
+        crossterm::style::SetForegroundColor(theme.text),
+    )
+    .unwrap();
+}
+
+// Decodes a whole file or URL into interleaved samples and returns them
+// with the channel count and sample rate. Decoding goes through symphonia
+// rather than rodio's decoder, which can't read streams and splices
+// packets of a different sample rate or channel count in as they are.
+fn load_samples(path: &str) -> Result<(Vec<f32>, usize, u32), String> {
+    let decoded = decode::decode_file(path, 1)?;
+    if decoded.format_changes > 0 {
+        eprintln!(
+            "Warning: {} changes its format {} times; {:.1} s were converted to {} Hz, {} channels",
+            path,
+            decoded.format_changes,
+            decoded.converted_secs,
+            decoded.sample_rate,
+            decoded.channels
+        );
+    }
+    Ok((decoded.samples, decoded.channels, decoded.sample_rate))
+}
+
+// The chain of --chain and its shorthands, with the --fir filter last.
+fn build_chain(
+    options: &cli::Options,
+    channels: usize,
+    sample_rate: u32,
+) -> Result<chain::ProcessorChain, String> {
+    let mut chain = chain::ProcessorChain::parse(&options.chain, channels, sample_rate)?;
+    if let Some(path) = &options.fir {
+        chain.push_fir(&fir::load_taps(path)?);
+    }
+    Ok(chain)
+}
+
+// The processed signal of `dry` for the difference view.
+// Replaces `out` with interleaved positions `range` of what the display
+// analyzes: the tap as playback publishes it, or the input itself while the
+// chain is bypassed or tapped before its first stage. False while playback
+// hasn't reached the end of the range.
+fn read_display(
+    feed: Option<&source::TapFeed>,
+    bypassed: bool,
+    input: &[f32],
+    range: std::ops::Range<usize>,
+    out: &mut Vec<f32>,
+) -> bool {
+    match feed {
+        Some(feed) if !bypassed => feed.read_into(range, out),
+        _ => {
+            out.clear();
+            out.extend_from_slice(&input[range]);
+            true
+        }
+    }
+}
+
+// The signal at the tap over interleaved positions `range` of it, rendered
+// from the input by `chain`. The chain starts a second earlier, so filters
+// and envelopes have settled from whatever it rendered before.
+fn render_window(
+    chain: &mut chain::ProcessorChain,
+    input: &[f32],
+    input_channels: usize,
+    sample_rate: u32,
+    range: std::ops::Range<usize>,
+) -> Vec<f32> {
+    let channels = chain.tap_channels();
+    if chain.tap == 0 {
+        return input[range].to_vec();
+    }
+    let (start, end) = (range.start / channels, range.end / channels);
+    let warmup = start.min((WINDOW_WARMUP_SECS * sample_rate as f32) as usize);
+    let mut rendered = chain.render_tap(
+        &input[(start - warmup) * input_channels..end * input_channels],
+        input_channels,
+    );
+    rendered.drain(..warmup * channels);
+    rendered
+}
+
+fn render_wet(
+    options: &cli::Options,
+    dry: &Arc<Vec<f32>>,
+    channels: usize,
+    sample_rate: u32,
+) -> Vec<f32> {
+    let chain = build_chain(options, channels, sample_rate).unwrap();
+    source::ProcessedSource::from_samples(Arc::clone(dry), channels as u16, sample_rate, chain)
+        .collect()
+}
+
+// Loads the files of --compare and matches their loudness with this one's,
+// which comes first. They need its sample rate and channel count, get the
+// same automation and are cut or padded with silence to its length, so all
+// play in sync.
+fn load_comparison(
+    options: &cli::Options,
+    samples: &[f32],
+    channels: usize,
+    sample_rate: u32,
+) -> Option<compare::Comparison> {
+    if options.compare.is_empty() {
+        return None;
+    }
+    let mut files = vec![(options.file_path.clone(), samples.to_vec())];
+    for path in &options.compare {
+        let loaded = load_samples(path).and_then(|(mut other, other_channels, other_rate)| {
+            if other_channels != channels || other_rate != sample_rate {
+                return Err(format!(
+                    "--compare {}: {} Hz, {} channels, but {} has {} Hz, {} channels",
+                    path, other_rate, other_channels, options.file_path, sample_rate, channels
+                ));
+            }
+            if let Some(spec) = &options.automation {
+                automation::GainEnvelope::parse(spec)?.apply(&mut other, channels, sample_rate);
+            }
+            other.resize(samples.len(), 0.0);
+            Ok(other)
+        });
+        match loaded {
+            Ok(other) => files.push((path.clone(), other)),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+    match compare::Comparison::new(files, channels, sample_rate) {
+        Ok(comparison) => {
+            for ((name, lufs), gain_db) in comparison
+                .names
+                .iter()
+                .zip(&comparison.lufs)
+                .zip(&comparison.gains_db)
+            {
+                println!(
+                    "Loudness match: {} at {:.1} LUFS, gain {:+.1} dB",
+                    name, lufs, gain_db
+                );
+            }
+            Some(comparison)
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn load_references(
+    options: &cli::Options,
+    spectrum: &mut spectrum::Spectrum,
+) -> Result<(), String> {
+    let mut overlays = Vec::new();
+    for path in &options.references {
+        let levels =
+            load_reference(path, spectrum).map_err(|e| format!("--reference {}: {}", path, e))?;
+        overlays.push(levels);
+    }
+    spectrum.overlays = overlays;
+    Ok(())
+}
+
+// Computes the average band levels of a reference track using the same band
+// layout as the live spectrum.
+fn load_reference(file_path: &str, spectrum: &spectrum::Spectrum) -> Result<Vec<f32>, String> {
+    let (samples, channels, sample_rate) = load_samples(file_path)?;
+    let mut reference_spectrum = spectrum::Spectrum::new(
+        spectrum.bands,
+        spectrum.min_db,
+        spectrum.max_db,
+        spectrum.smooth_factor,
+        spectrum.fft_size,
+        sample_rate,
+    );
+    reference_spectrum.min_freq = spectrum.min_freq;
+    reference_spectrum.max_freq = spectrum.max_freq;
+    reference_spectrum.channels = channels;
+    reference_spectrum.set_zero_pad_factor(spectrum.zero_pad_factor);
+    reference_spectrum.channel_mode = spectrum.channel_mode;
+    reference_spectrum.window = spectrum.window;
+    reference_spectrum.enbw_correction = spectrum.enbw_correction;
+    reference_spectrum.include_dc = spectrum.include_dc;
+    Ok(analysis::average_band_levels(&reference_spectrum, &samples))
+}
+
+// Writes every frame range of `processed` to <prefix>_NN.wav.
+fn write_segments(
+    options: &cli::Options,
+    prefix: &str,
+    processed: &[f32],
+    channels: usize,
+    sample_rate: u32,
+    ranges: &[(usize, usize)],
+) {
+    for (i, &(start, end)) in ranges.iter().enumerate() {
+        let path = format!("{}_{:02}.wav", prefix, i + 1);
+        let mut ditherer = dither::Ditherer::new(
+            options.bits,
+            channels,
+            options.dither_enabled(),
+            options.noise_shaping,
+        );
+        export::write_wav(
+            &path,
+            &processed[start * channels..end * channels],
+            channels as u16,
+            sample_rate,
+            options.bits,
+            &mut ditherer,
+        )
+        .unwrap();
+        println!(
+            "{}: {} - {}",
+            path,
+            format_duration_millis(start as f32 / sample_rate as f32),
+            format_duration_millis(end as f32 / sample_rate as f32)
+        );
+    }
+}
+
+// Draws the embedded cover art above the spectrum and returns the number of
+// rows it takes, 0 when there is none or it can't be decoded.
+#[cfg(feature = "cover-art")]
+fn draw_cover_art(file_path: &str) -> u16 {
+    match cover::load(file_path) {
+        Some(art) if cover::draw(&art, cover::ART_ROWS, &mut stdout()) => cover::ART_ROWS + 1,
+        _ => 0,
+    }
+}
+
+#[cfg(not(feature = "cover-art"))]
+fn draw_cover_art(_file_path: &str) -> u16 {
+    0
 }
 
-fn load_audio(file_path: &str) -> Result<RodioDecoder<BufReader<File>>, String> {
-    let file = File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
-    let reader = BufReader::new(file);
-    let decoder =
-        RodioDecoder::new(reader).map_err(|e| format!("Failed to decode audio: {}", e))?;
-    Ok(decoder)
+fn format_duration_millis(seconds: f32) -> String {
+    let millis = ((seconds % 1.0) * 1000.0).floor() as u32;
+    format!("{}.{:03}", format_duration(seconds), millis)
 }
 
 fn format_duration(seconds: f32) -> String {
@@ -0,0 +1,118 @@
+use crate::analysis;
+use crate::loudness::{self, KWeighting};
+use crate::weighting::{WeightedPeak, Weighting};
+
+// Rate at which the peak readout falls once the signal drops.
+const PEAK_RELEASE_DB_PER_SEC: f32 = 20.0;
+
+// RMS and loudness readouts of the playing signal. Both average power with
+// an exponential moving average whose time constant is the meter window,
+// like the ballistics of a hardware meter: 400 ms reads like EBU
+// "momentary" loudness and 3 s like "short-term". The smoothing of every
+// update follows from the length of the samples it is given, so it holds
+// when the hop changes. Levels are read after `input_gain`, as in the
+// spectrum and the report.
+pub struct LevelMeter {
+    window_secs: f32,
+    input_gain: f32,
+    sample_rate: u32,
+    channels: usize,
+    weighting: KWeighting,
+    // Smoothed mean square of the samples and K-weighted power.
+    power: f32,
+    weighted_power: f64,
+}
+
+impl LevelMeter {
+    pub fn new(window_secs: f32, input_gain: f32, channels: usize, sample_rate: u32) -> Self {
+        LevelMeter {
+            window_secs,
+            input_gain,
+            sample_rate,
+            channels,
+            weighting: KWeighting::new(channels, sample_rate),
+            power: 0.0,
+            weighted_power: 0.0,
+        }
+    }
+
+    // Adds the interleaved samples played since the last update.
+    pub fn update(&mut self, samples: &[f32]) {
+        let frames = samples.len() / self.channels;
+        if frames == 0 {
+            return;
+        }
+        let secs = frames as f32 / self.sample_rate as f32;
+        let alpha = 1.0 - (-secs / self.window_secs).exp();
+        // The K filter is linear, so the gain scales its power as well.
+        let gain_squared = self.input_gain * self.input_gain;
+        let power =
+            samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32 * gain_squared;
+        let weighted = samples
+            .chunks_exact(self.channels)
+            .map(|frame| self.weighting.frame_power(frame) as f64)
+            .sum::<f64>()
+            / frames as f64
+            * gain_squared as f64;
+        self.power += alpha * (power - self.power);
+        self.weighted_power += alpha as f64 * (weighted - self.weighted_power);
+    }
+
+    pub fn rms_db(&self) -> f32 {
+        analysis::to_db(self.power.sqrt())
+    }
+
+    pub fn lufs(&self) -> f32 {
+        loudness::to_lufs(self.weighted_power)
+    }
+
+    // Readout for the status line, e.g. "RMS -18.2 dBFS, -16.0 LUFS (3000 ms)".
+    pub fn status(&self) -> String {
+        format!(
+            "RMS {:.1} dBFS, {:.1} LUFS ({:.0} ms)",
+            self.rms_db(),
+            self.lufs().max(-99.9),
+            self.window_secs * 1000.0
+        )
+    }
+}
+
+// Peak readout of the playing signal behind a weighting filter, e.g. to
+// watch the A-weighted peak. It jumps to every new peak and falls back at
+// `PEAK_RELEASE_DB_PER_SEC` once the signal drops. Like the level meter it
+// reads after `input_gain`.
+pub struct PeakMeter {
+    detector: WeightedPeak,
+    input_gain: f32,
+    sample_rate: u32,
+    channels: usize,
+    peak_db: f32,
+}
+
+impl PeakMeter {
+    pub fn new(weighting: Weighting, input_gain: f32, channels: usize, sample_rate: u32) -> Self {
+        PeakMeter {
+            detector: WeightedPeak::new(weighting, channels, sample_rate),
+            input_gain,
+            sample_rate,
+            channels,
+            peak_db: f32::NEG_INFINITY,
+        }
+    }
+
+    // Adds the interleaved samples played since the last update.
+    pub fn update(&mut self, samples: &[f32]) {
+        let secs = (samples.len() / self.channels) as f32 / self.sample_rate as f32;
+        let peak_db = analysis::to_db(self.detector.peak(samples) * self.input_gain);
+        self.peak_db = (self.peak_db - PEAK_RELEASE_DB_PER_SEC * secs).max(peak_db);
+    }
+
+    // Readout for the status line, e.g. "Peak (A) -6.1 dBFS".
+    pub fn status(&self) -> String {
+        format!(
+            "Peak ({}) {:.1} dBFS",
+            self.detector.weighting.label(),
+            self.peak_db.max(-99.9)
+        )
+    }
+}
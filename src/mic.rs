@@ -0,0 +1,148 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, FromSample, SampleFormat, SizedSample};
+
+use crate::source::Chunk;
+
+// Audio shown on the live display as it arrives, see `monitor_live`.
+pub trait LiveInput {
+    // What the status line says about the input, e.g. "Recording from mic".
+    fn label(&self) -> String;
+    fn sample_rate(&self) -> u32;
+    fn channels(&self) -> usize;
+    // Everything that arrived since the last call, or the error that ended
+    // the input.
+    fn receive(&self) -> Result<Vec<f32>, String>;
+    // Whether nothing more will arrive; a recording runs until it is quit.
+    fn finished(&self) -> bool {
+        false
+    }
+    fn set_paused(&self, _paused: bool) {}
+}
+
+// Live recording from an input device in its default format. The audio
+// thread sends every buffer it gets to the main thread, which collects them
+// with `receive`.
+pub struct MicInput {
+    pub name: String,
+    pub sample_rate: u32,
+    pub channels: usize,
+    receiver: Receiver<Chunk>,
+    // Recording stops when the stream is dropped.
+    _stream: cpal::Stream,
+}
+
+impl MicInput {
+    // Opens the input device called `device_name`, or the default one.
+    pub fn open(device_name: Option<&str>) -> Result<Self, String> {
+        let host = cpal::default_host();
+        let device = match device_name {
+            Some(name) => find_device(&host, name)?,
+            None => host
+                .default_input_device()
+                .ok_or_else(|| "No input device found".to_string())?,
+        };
+        let name = device
+            .name()
+            .unwrap_or_else(|_| "the input device".to_string());
+        let config = device
+            .default_input_config()
+            .map_err(|e| format!("Failed to query {}: {}", name, e))?;
+
+        let (sender, receiver) = mpsc::channel();
+        let stream_config = config.config();
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, sender),
+            SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, sender),
+            SampleFormat::I32 => build_stream::<i32>(&device, &stream_config, sender),
+            SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, sender),
+            SampleFormat::U8 => build_stream::<u8>(&device, &stream_config, sender),
+            other => {
+                return Err(format!(
+                    "{} records {} samples, which are not supported",
+                    name, other
+                ));
+            }
+        }
+        .map_err(|e| format!("Failed to open {}: {}", name, e))?;
+        stream
+            .play()
+            .map_err(|e| format!("Failed to start recording from {}: {}", name, e))?;
+
+        Ok(MicInput {
+            name,
+            sample_rate: config.sample_rate().0,
+            channels: config.channels() as usize,
+            receiver,
+            _stream: stream,
+        })
+    }
+}
+
+impl LiveInput for MicInput {
+    fn label(&self) -> String {
+        format!("Recording from {}", self.name)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    // Everything recorded since the last call, or the error that ended the
+    // recording, e.g. when the device was unplugged.
+    fn receive(&self) -> Result<Vec<f32>, String> {
+        let mut samples = Vec::new();
+        for chunk in self.receiver.try_iter() {
+            samples.extend(chunk?);
+        }
+        Ok(samples)
+    }
+}
+
+fn find_device(host: &cpal::Host, name: &str) -> Result<cpal::Device, String> {
+    let devices: Vec<cpal::Device> = host
+        .input_devices()
+        .map_err(|e| format!("Failed to list input devices: {}", e))?
+        .collect();
+    let names: Vec<String> = devices
+        .iter()
+        .map(|device| device.name().unwrap_or_default())
+        .collect();
+    match names.iter().position(|candidate| candidate == name) {
+        Some(index) => Ok(devices.into_iter().nth(index).unwrap()),
+        None if names.is_empty() => Err("No input device found".to_string()),
+        None => Err(format!(
+            "No input device named '{}', available: {}",
+            name,
+            names.join(", ")
+        )),
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sender: Sender<Chunk>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let errors = sender.clone();
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            // The receiver is only gone while shutting down.
+            let _ = sender.send(Ok(data.iter().map(|&s| f32::from_sample_(s)).collect()));
+        },
+        move |error| {
+            let _ = errors.send(Err(format!("Recording failed: {}", error)));
+        },
+        None,
+    )
+}
@@ -0,0 +1,97 @@
+use std::fs;
+
+// 480 ticks per quarter note at the default 120 BPM gives 960 ticks a second.
+const TICKS_PER_QUARTER: u16 = 480;
+const TICKS_PER_SECOND: f32 = 960.0;
+const VELOCITY: u8 = 100;
+
+pub struct Note {
+    pub note: u8,
+    pub start: f32,
+    pub end: f32,
+}
+
+// Turns a per-frame note track into sustained notes, merging consecutive
+// frames with the same note. `None` frames end the current note.
+pub fn notes_from_track(track: &[Option<i32>], frame_secs: f32) -> Vec<Note> {
+    let mut notes: Vec<Note> = Vec::new();
+    let mut current: Option<(i32, usize)> = None;
+    for (i, &frame) in track.iter().chain(std::iter::once(&None)).enumerate() {
+        if current.map(|(note, _)| Some(note)) == Some(frame) {
+            continue;
+        }
+        if let Some((note, start)) = current.take() {
+            notes.push(Note {
+                note: note.clamp(0, 127) as u8,
+                start: start as f32 * frame_secs,
+                end: i as f32 * frame_secs,
+            });
+        }
+        current = frame.map(|note| (note, i));
+    }
+    notes
+}
+
+// Writes a format 0 standard MIDI file with a single monophonic track.
+pub fn write_midi(path: &str, notes: &[Note]) -> Result<(), String> {
+    let mut track = Vec::new();
+    let mut last_tick = 0;
+    for note in notes {
+        // A note shorter than a tick is stretched to one, so the next may
+        // then start before the previous one ends; it waits for it instead.
+        let start = ((note.start * TICKS_PER_SECOND) as u32).max(last_tick);
+        let end = ((note.end * TICKS_PER_SECOND) as u32).max(start + 1);
+        write_var_len(&mut track, start.saturating_sub(last_tick));
+        track.extend_from_slice(&[0x90, note.note, VELOCITY]);
+        write_var_len(&mut track, end - start);
+        track.extend_from_slice(&[0x80, note.note, 0]);
+        last_tick = end;
+    }
+    // End of track meta event.
+    track.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+    let mut file = Vec::new();
+    file.extend_from_slice(b"MThd");
+    file.extend_from_slice(&6u32.to_be_bytes());
+    file.extend_from_slice(&0u16.to_be_bytes());
+    file.extend_from_slice(&1u16.to_be_bytes());
+    file.extend_from_slice(&TICKS_PER_QUARTER.to_be_bytes());
+    file.extend_from_slice(b"MTrk");
+    file.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    file.extend_from_slice(&track);
+
+    fs::write(path, file).map_err(|e| format!("Failed to write MIDI file: {}", e))
+}
+
+fn write_var_len(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_shorter_than_a_tick_follow_each_other() {
+        let path = std::env::temp_dir().join(format!("sound_heat_midi_{}.mid", std::process::id()));
+        // Frames of 0.5 ms are under the 1.04 ms of a tick.
+        let notes = notes_from_track(&[Some(60), Some(62), Some(64)], 0.0005);
+        write_midi(path.to_str().unwrap(), &notes).unwrap();
+        let file = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let track = &file[22..];
+        assert_eq!(
+            track,
+            [
+                0, 0x90, 60, VELOCITY, 1, 0x80, 60, 0, 0, 0x90, 62, VELOCITY, 1, 0x80, 62, 0, 0,
+                0x90, 64, VELOCITY, 1, 0x80, 64, 0, 0x00, 0xFF, 0x2F, 0x00,
+            ]
+        );
+    }
+}
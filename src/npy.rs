@@ -0,0 +1,28 @@
+use std::fs;
+
+// Writes a row-major 2-D matrix of f32 in NumPy's `.npy` format, version 1.0.
+pub fn write_npy(path: &str, rows: &[Vec<f32>], columns: usize) -> Result<(), String> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        rows.len(),
+        columns
+    );
+    // Magic, version and header length take 10 bytes; the header is padded
+    // with spaces and a newline so the data starts 64-byte aligned.
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut file = Vec::with_capacity(10 + header.len() + rows.len() * columns * 4);
+    file.extend_from_slice(b"\x93NUMPY");
+    file.extend_from_slice(&[1, 0]);
+    file.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    file.extend_from_slice(header.as_bytes());
+    for row in rows {
+        for value in row {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fs::write(path, file).map_err(|e| format!("Failed to write NumPy file: {}", e))
+}
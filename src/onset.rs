@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+// Spectral flux onset detector working on per-band levels. The flux of a frame
+// is the summed rise in dB over the previous frame; an onset is reported when
+// it clearly exceeds the recent average flux.
+pub struct OnsetDetector {
+    previous: Vec<f32>,
+    history: VecDeque<f32>,
+    history_len: usize,
+    frames_since_onset: usize,
+}
+
+// Flux must exceed the recent average by this factor and margin.
+const THRESHOLD_FACTOR: f32 = 1.5;
+const THRESHOLD_MARGIN_DB: f32 = 10.0;
+// Frames to ignore after an onset so one hit isn't reported twice.
+const MIN_ONSET_GAP: usize = 3;
+
+impl OnsetDetector {
+    pub fn new(history_len: usize) -> Self {
+        OnsetDetector {
+            previous: Vec::new(),
+            history: VecDeque::with_capacity(history_len),
+            history_len,
+            frames_since_onset: MIN_ONSET_GAP,
+        }
+    }
+
+    // Returns the flux of the frame and whether it is an onset. `floor_db`
+    // keeps near-silent bands from contributing noise.
+    pub fn process(&mut self, levels: &[f32], floor_db: f32) -> (f32, bool) {
+        let flux = if self.previous.len() == levels.len() {
+            levels
+                .iter()
+                .zip(&self.previous)
+                .map(|(&db, &prev)| (db.max(floor_db) - prev.max(floor_db)).max(0.0))
+                .sum()
+        } else {
+            0.0
+        };
+        self.previous = levels.to_vec();
+
+        let average = if self.history.is_empty() {
+            f32::INFINITY
+        } else {
+            self.history.iter().sum::<f32>() / self.history.len() as f32
+        };
+        if self.history.len() == self.history_len {
+            self.history.pop_front();
+        }
+        self.history.push_back(flux);
+
+        self.frames_since_onset += 1;
+        let onset = flux > average * THRESHOLD_FACTOR + THRESHOLD_MARGIN_DB
+            && self.frames_since_onset > MIN_ONSET_GAP;
+        if onset {
+            self.frames_since_onset = 0;
+        }
+        (flux, onset)
+    }
+}
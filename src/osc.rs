@@ -0,0 +1,52 @@
+use std::net::UdpSocket;
+
+use rosc::{OscMessage, OscPacket, OscType, encoder};
+
+use crate::spectrum::FrameInfo;
+
+// Sends the band levels of every frame, and a trigger on every onset, as OSC
+// messages over UDP.
+pub struct OscSender {
+    socket: UdpSocket,
+    pub bands_address: String,
+    pub beat_address: String,
+}
+
+impl OscSender {
+    pub fn new(target: &str, bands_address: &str, beat_address: &str) -> Result<Self, String> {
+        let socket =
+            UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to open socket: {}", e))?;
+        socket
+            .connect(target)
+            .map_err(|e| format!("Invalid OSC target {}: {}", target, e))?;
+        Ok(OscSender {
+            socket,
+            bands_address: bands_address.to_string(),
+            beat_address: beat_address.to_string(),
+        })
+    }
+
+    pub fn send(&self, info: &FrameInfo) {
+        self.send_message(
+            &self.bands_address,
+            info.band_levels
+                .iter()
+                .map(|&db| OscType::Float(db))
+                .collect(),
+        );
+        if info.onset {
+            self.send_message(&self.beat_address, vec![OscType::Int(1)]);
+        }
+    }
+
+    fn send_message(&self, address: &str, args: Vec<OscType>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: address.to_string(),
+            args,
+        });
+        // Visuals tolerate dropped frames, so send errors are ignored.
+        if let Ok(bytes) = encoder::encode(&packet) {
+            let _ = self.socket.send(&bytes);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::analysis;
+use crate::spectrum::Spectrum;
+
+pub const A4: f32 = 440.0;
+const HPS_HARMONICS: usize = 5;
+const MIN_PITCH: f32 = 50.0;
+const MAX_PITCH: f32 = 2000.0;
+// Harmonics weaker than this fraction of the strongest peak count as absent.
+const HARMONIC_PRESENT: f32 = 0.1;
+// Frames quieter than this are treated as silence rather than a pitch.
+pub const SILENCE_DB: f32 = -50.0;
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+// Harmonic product spectrum: multiplying the spectrum with copies of itself
+// compressed by 2, 3, ... reinforces the fundamental, which is shared by all
+// harmonics, over any single strong overtone. The product is taken as a sum of
+// logs. HPS is prone to picking a subharmonic when the overtones are weak (a
+// pure sine), since then every bin reaching the one strong peak scores alike.
+// So the candidate is multiplied by the largest factor shared by all its
+// harmonics that carry energy: a sine at a third of the candidate has only
+// the third, while a tone with a weak or missing fundamental keeps its
+// consecutive overtones and stays where it is. The frequency is refined by
+// parabolic interpolation on the strongest of those harmonics.
+pub fn hps_pitch(magnitudes: &[f32], sample_rate: u32, fft_size: usize) -> Option<f32> {
+    let bin_width = sample_rate as f32 / fft_size as f32;
+    let half = magnitudes.len() / 2;
+    let low_bin = ((MIN_PITCH / bin_width).floor() as usize).max(1);
+    let high_bin = ((MAX_PITCH / bin_width).ceil() as usize).min(half / HPS_HARMONICS);
+    if low_bin + 2 >= high_bin {
+        return None;
+    }
+
+    let hps: Vec<f32> = (0..=high_bin)
+        .map(|bin| {
+            (1..=HPS_HARMONICS)
+                .map(|h| (magnitudes[bin * h] + 1e-10).ln())
+                .sum()
+        })
+        .collect();
+
+    let peak = (low_bin..high_bin).max_by(|&a, &b| hps[a].total_cmp(&hps[b]))?;
+    let strongest = magnitudes[low_bin..half]
+        .iter()
+        .fold(0.0f32, |m, &v| m.max(v));
+    // The local maximum around harmonic `h` of the candidate, whose bin is
+    // off by up to h / 2 from rounding the candidate.
+    let harmonic_bin = |h: usize| {
+        let (centre, radius) = (peak * h, h.div_ceil(2));
+        (centre.saturating_sub(radius).max(1)..=(centre + radius).min(half - 2))
+            .max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))
+            .unwrap_or(centre)
+    };
+    // Only a peak counts, not the skirt of a neighbouring one, and a peak
+    // in reach of two harmonics is the one it lies closest to.
+    let distance = |h: usize| harmonic_bin(h).abs_diff(peak * h);
+    let present: Vec<usize> = (1..=HPS_HARMONICS)
+        .filter(|&h| {
+            let bin = harmonic_bin(h);
+            magnitudes[bin] >= HARMONIC_PRESENT * strongest
+                && magnitudes[bin] >= magnitudes[bin - 1]
+                && magnitudes[bin] >= magnitudes[bin + 1]
+                && (1..=HPS_HARMONICS).all(|other| {
+                    other == h || harmonic_bin(other) != bin || distance(other) > distance(h)
+                })
+        })
+        .collect();
+    let multiple = present.iter().copied().reduce(gcd).unwrap_or(1);
+    let harmonic = present
+        .iter()
+        .copied()
+        .max_by(|&a, &b| magnitudes[harmonic_bin(a)].total_cmp(&magnitudes[harmonic_bin(b)]))
+        .unwrap_or(1);
+
+    let bin = harmonic_bin(harmonic);
+    let ln = |bin: usize| (magnitudes[bin] + 1e-10).ln();
+    let (left, centre, right) = (ln(bin - 1), ln(bin), ln(bin + 1));
+    let denominator = left - 2.0 * centre + right;
+    let offset = if denominator.abs() > f32::EPSILON {
+        (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    Some((bin as f32 + offset) * bin_width * multiple as f32 / harmonic as f32)
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// Dominant frequency of every hop, or `None` for silent frames.
+pub fn pitch_track(spectrum: &Spectrum, mono: &[f32], hop_size: usize) -> Vec<Option<f32>> {
+    analysis::windows(mono, spectrum.fft_size, hop_size)
+        .map(|frame| {
+            if analysis::to_db(analysis::rms(frame)) < SILENCE_DB {
+                None
+            } else {
+                hps_pitch(
+                    &spectrum.magnitudes(frame),
+                    spectrum.sample_rate,
+                    spectrum.fft_len(),
+                )
+            }
+        })
+        .collect()
+}
+
+// Scientific pitch name of a MIDI note, e.g. 69 is A4.
+pub fn note_name(note: i32) -> String {
+    format!(
+        "{}{}",
+        NOTE_NAMES[note.rem_euclid(12) as usize],
+        note.div_euclid(12) - 1
+    )
+}
+
+// Nearest MIDI note and the deviation from it in cents.
+pub fn frequency_to_note(freq: f32, a4: f32) -> (i32, f32) {
+    let semitones = 69.0 + 12.0 * (freq / a4).log2();
+    let note = semitones.round();
+    (note as i32, (semitones - note) * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+    const FFT_SIZE: usize = 4096;
+
+    // Pitch of a tone of the given harmonics of `freq`, `(harmonic, amplitude)`.
+    fn pitch_of(freq: f32, harmonics: &[(usize, f32)]) -> Option<f32> {
+        let tone: Vec<f32> = (0..FFT_SIZE)
+            .map(|n| {
+                let t = n as f32 / SAMPLE_RATE as f32;
+                harmonics
+                    .iter()
+                    .map(|&(h, a)| a * (2.0 * std::f32::consts::PI * freq * h as f32 * t).sin())
+                    .sum()
+            })
+            .collect();
+        let spectrum = Spectrum::new(32, -100.0, 0.0, 0.0, FFT_SIZE, SAMPLE_RATE);
+        hps_pitch(&spectrum.magnitudes(&tone), SAMPLE_RATE, spectrum.fft_len())
+    }
+
+    fn assert_pitch(freq: f32, harmonics: &[(usize, f32)]) {
+        let pitch = pitch_of(freq, harmonics).unwrap();
+        let cents = 1200.0 * (pitch / freq).log2();
+        assert!(cents.abs() < 50.0, "{} Hz read as {} Hz", freq, pitch);
+    }
+
+    #[test]
+    fn pure_sine() {
+        for freq in [82.4, 220.0, 440.0, 1000.0] {
+            assert_pitch(freq, &[(1, 0.5)]);
+        }
+    }
+
+    #[test]
+    fn tone_with_harmonics() {
+        for freq in [110.0, 196.0, 440.0] {
+            assert_pitch(freq, &[(1, 0.5), (2, 0.25), (3, 0.15), (4, 0.1)]);
+        }
+    }
+
+    #[test]
+    fn missing_fundamental() {
+        for freq in [110.0, 196.0, 300.0] {
+            assert_pitch(freq, &[(2, 0.3), (3, 0.3), (4, 0.2), (5, 0.15)]);
+        }
+    }
+
+    #[test]
+    fn weak_fundamental() {
+        for freq in [110.0, 196.0] {
+            assert_pitch(freq, &[(1, 0.01), (2, 0.3), (3, 0.3), (4, 0.2), (5, 0.1)]);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::png;
+
+pub type Rgb = [u8; 3];
+
+const BACKGROUND: Rgb = [20, 22, 30];
+const GRID: Rgb = [55, 58, 70];
+const TEXT: Rgb = [210, 210, 210];
+const LINE: Rgb = [90, 200, 250];
+const REFERENCE: Rgb = [250, 170, 40];
+// Colormap of the spectrogram from its quietest to its loudest level, dark
+// to bright so it also reads in greyscale, after matplotlib's "inferno".
+const HEAT_STOPS: [Rgb; 5] = [
+    [0, 0, 4],
+    [87, 16, 110],
+    [188, 55, 84],
+    [249, 142, 9],
+    [252, 255, 164],
+];
+
+// 3x5 pixel glyphs, one row per byte with the leftmost pixel in bit 2.
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+
+pub fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'S' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'd' => [0b001, 0b001, 0b111, 0b101, 0b111],
+        'k' => [0b100, 0b101, 0b110, 0b101, 0b101],
+        's' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'z' => [0b000, 0b111, 0b010, 0b100, 0b111],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
+
+// RGB image with a few drawing primitives for graphs.
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: background.repeat(width * height),
+        }
+    }
+
+    // Pixels outside the canvas are ignored.
+    pub fn set(&mut self, x: isize, y: isize, color: Rgb) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            let i = (y as usize * self.width + x as usize) * 3;
+            self.pixels[i..i + 3].copy_from_slice(&color);
+        }
+    }
+
+    pub fn line(&mut self, from: (isize, isize), to: (isize, isize), color: Rgb) {
+        // Bresenham's algorithm, for all octants.
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let step_x = if x < to.0 { 1 } else { -1 };
+        let step_y = if y < to.1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            self.set(x, y, color);
+            if (x, y) == to {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    // Horizontal line drawn with `dash` pixels on and off, solid for 0.
+    pub fn hline(&mut self, x0: isize, x1: isize, y: isize, dash: isize, color: Rgb) {
+        for x in x0..=x1 {
+            if dash == 0 || (x - x0) / dash % 2 == 0 {
+                self.set(x, y, color);
+            }
+        }
+    }
+
+    pub fn text_width(text: &str, scale: usize) -> usize {
+        text.chars().count() * (GLYPH_WIDTH + 1) * scale
+    }
+
+    // Draws text with its top left corner at (x, y), every glyph pixel
+    // `scale` pixels wide.
+    pub fn text(&mut self, x: isize, y: isize, text: &str, scale: usize, color: Rgb) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + (i * (GLYPH_WIDTH + 1) * scale) as isize;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 0 {
+                        continue;
+                    }
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            self.set(
+                                left + (column * scale + sx) as isize,
+                                y + (row * scale + sy) as isize,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn write_png(&self, path: &str) -> Result<(), String> {
+        png::write_png(path, self.width, self.height, &self.pixels)
+    }
+}
+
+// Tick spacing giving at most `max_ticks` ticks over `range`.
+fn tick_step(range: f32, max_ticks: usize, steps: &[f32]) -> f32 {
+    steps
+        .iter()
+        .copied()
+        .find(|step| range / step <= max_ticks as f32)
+        .unwrap_or(steps[steps.len() - 1])
+}
+
+// Plots short-term loudness over time as a line, with the integrated value
+// as a dashed reference line. `values` are in LUFS, the first one at
+// `start_secs` and the rest every `hop_secs`.
+pub fn loudness_graph(
+    path: &str,
+    values: &[f32],
+    start_secs: f32,
+    hop_secs: f32,
+    duration_secs: f32,
+    integrated: Option<f32>,
+) -> Result<(), String> {
+    let (width, height) = (1200, 420);
+    let (left, right, top, bottom) = (70, 20, 20, 50);
+    let scale = 2;
+    let mut canvas = Canvas::new(width, height, BACKGROUND);
+    let plot_width = (width - left - right) as f32;
+    let plot_height = (height - top - bottom) as f32;
+
+    // Loudness from the quietest audible value, in steps of 10 LU, to 0 LUFS.
+    let quietest = values
+        .iter()
+        .copied()
+        .filter(|v| v.is_finite())
+        .fold(0.0f32, f32::min);
+    let min_lufs = ((quietest.max(-70.0) - 5.0) / 10.0).floor() * 10.0;
+    let x_of = |secs: f32| left as isize + (secs / duration_secs.max(1e-3) * plot_width) as isize;
+    let y_of = |lufs: f32| {
+        let fraction = (lufs.clamp(min_lufs, 0.0) - min_lufs) / -min_lufs;
+        top as isize + ((1.0 - fraction) * plot_height) as isize
+    };
+
+    let mut lufs = min_lufs;
+    while lufs <= 0.0 {
+        let y = y_of(lufs);
+        canvas.hline(left as isize, (width - right) as isize, y, 0, GRID);
+        let label = format!("{}", lufs);
+        let label_x = left as isize - 8 - Canvas::text_width(&label, scale) as isize;
+        canvas.text(label_x, y - 5, &label, scale, TEXT);
+        lufs += 10.0;
+    }
+    let step = tick_step(
+        duration_secs,
+        12,
+        &[
+            1.0, 2.0, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0,
+        ],
+    );
+    let mut secs = 0.0;
+    while secs <= duration_secs {
+        let x = x_of(secs);
+        canvas.line((x, top as isize), (x, (height - bottom) as isize), GRID);
+        let label = format!("{}", secs);
+        let label_x = x - Canvas::text_width(&label, scale) as isize / 2;
+        canvas.text(label_x, (height - bottom + 8) as isize, &label, scale, TEXT);
+        secs += step;
+    }
+    let unit_x = (width / 2) as isize - Canvas::text_width("s", scale) as isize / 2;
+    canvas.text(unit_x, (height - 18) as isize, "s", scale, TEXT);
+    canvas.text(4, 4, "LUFS", scale, TEXT);
+
+    if let Some(integrated) = integrated {
+        let y = y_of(integrated);
+        canvas.hline(left as isize, (width - right) as isize, y, 6, REFERENCE);
+        let label = format!("I {:.1} LUFS", integrated);
+        let label_x = (width - right) as isize - Canvas::text_width(&label, scale) as isize;
+        canvas.text(label_x, y - 14, &label, scale, REFERENCE);
+    }
+
+    let points: Vec<(isize, isize)> = values
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| (x_of(start_secs + i as f32 * hop_secs), y_of(v)))
+        .collect();
+    for pair in points.windows(2) {
+        canvas.line(pair[0], pair[1], LINE);
+    }
+
+    canvas.write_png(path)
+}
+
+// Color of `fraction` from 0 to 1 on the spectrogram's colormap.
+fn heat_color(fraction: f32) -> Rgb {
+    let position = fraction.clamp(0.0, 1.0) * (HEAT_STOPS.len() - 1) as f32;
+    let low = (position as usize).min(HEAT_STOPS.len() - 2);
+    let t = position - low as f32;
+    let mut color = [0; 3];
+    for (i, value) in color.iter_mut().enumerate() {
+        let (a, b) = (HEAT_STOPS[low][i] as f32, HEAT_STOPS[low + 1][i] as f32);
+        *value = (a + (b - a) * t).round() as u8;
+    }
+    color
+}
+
+// Short frequency label, e.g. 500 or 2k.
+fn frequency_label(freq: f32) -> String {
+    if freq >= 1000.0 {
+        format!("{}k", freq / 1000.0)
+    } else {
+        format!("{}", freq)
+    }
+}
+
+// Draws a spectrogram with one pixel per value, columns over time and rows
+// over log frequency from `min_freq` to `max_freq`, lowest row first, as
+// from `spectrogram::spectrogram`. Levels from `min_db` to `max_db` span the
+// colormap, which is shown with its scale as a colorbar on the right.
+pub fn spectrogram_image(
+    path: &str,
+    columns: &[Vec<f32>],
+    duration_secs: f32,
+    (min_freq, max_freq): (f32, f32),
+    (min_db, max_db): (f32, f32),
+) -> Result<(), String> {
+    let (plot_width, plot_height) = (columns.len(), columns.first().map_or(0, Vec::len));
+    let (left, right, top, bottom) = (70, 110, 30, 50);
+    let (width, height) = (left + plot_width + right, top + plot_height + bottom);
+    let scale = 2;
+    let mut canvas = Canvas::new(width, height, BACKGROUND);
+
+    for (x, column) in columns.iter().enumerate() {
+        for (row, &db) in column.iter().enumerate() {
+            let y = top + plot_height - 1 - row;
+            let color = heat_color((db - min_db) / (max_db - min_db));
+            canvas.set((left + x) as isize, y as isize, color);
+        }
+    }
+
+    // Frequency ticks at 1, 2 and 5 times every power of ten in range.
+    let y_of_freq = |freq: f32| {
+        let fraction = (freq / min_freq).ln() / (max_freq / min_freq).ln();
+        (top + plot_height) as isize - (fraction * plot_height as f32) as isize
+    };
+    let mut decade = 10f32.powf(min_freq.log10().floor());
+    while decade <= max_freq {
+        for freq in [decade, 2.0 * decade, 5.0 * decade] {
+            if freq < min_freq || freq > max_freq {
+                continue;
+            }
+            let y = y_of_freq(freq);
+            canvas.hline(left as isize - 5, left as isize - 1, y, 0, TEXT);
+            let label = frequency_label(freq);
+            let label_x = left as isize - 9 - Canvas::text_width(&label, scale) as isize;
+            canvas.text(label_x, y - 5, &label, scale, TEXT);
+        }
+        decade *= 10.0;
+    }
+    canvas.text(4, 4, "Hz", scale, TEXT);
+
+    let step = tick_step(
+        duration_secs,
+        (plot_width / 100).max(2),
+        &[
+            0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0,
+        ],
+    );
+    let bottom_edge = (top + plot_height) as isize;
+    let mut tick = 0;
+    while tick as f32 * step <= duration_secs {
+        let secs = tick as f32 * step;
+        let x = left as isize + (secs / duration_secs.max(1e-3) * plot_width as f32) as isize;
+        canvas.line((x, bottom_edge + 1), (x, bottom_edge + 5), TEXT);
+        // Rounded, so steps below a second don't print float noise.
+        let label = format!("{}", (secs * 10.0).round() / 10.0);
+        let label_x = x - Canvas::text_width(&label, scale) as isize / 2;
+        canvas.text(label_x, bottom_edge + 10, &label, scale, TEXT);
+        tick += 1;
+    }
+    let unit_x = (left + plot_width / 2) as isize - Canvas::text_width("s", scale) as isize / 2;
+    canvas.text(unit_x, (height - 18) as isize, "s", scale, TEXT);
+
+    // Colorbar with a tick every few dB.
+    let bar_left = (left + plot_width + 20) as isize;
+    let bar_width = 16;
+    for y in 0..plot_height {
+        let fraction = 1.0 - y as f32 / (plot_height - 1).max(1) as f32;
+        for x in 0..bar_width {
+            canvas.set(bar_left + x, (top + y) as isize, heat_color(fraction));
+        }
+    }
+    let y_of_db = |db: f32| {
+        let fraction = (db - min_db) / (max_db - min_db);
+        bottom_edge - 1 - (fraction * (plot_height - 1) as f32) as isize
+    };
+    let step = tick_step(max_db - min_db, 10, &[1.0, 2.0, 5.0, 10.0, 20.0, 50.0]);
+    let mut db = (min_db / step).ceil() * step;
+    while db <= max_db {
+        let y = y_of_db(db);
+        canvas.hline(bar_left + bar_width, bar_left + bar_width + 4, y, 0, TEXT);
+        canvas.text(
+            bar_left + bar_width + 8,
+            y - 5,
+            &format!("{}", db),
+            scale,
+            TEXT,
+        );
+        db += step;
+    }
+    canvas.text(bar_left, 4, "dB", scale, TEXT);
+
+    canvas.write_png(path)
+}
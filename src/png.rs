@@ -0,0 +1,72 @@
+use std::fs;
+
+// Largest payload of a stored deflate block.
+const STORED_BLOCK: usize = 65535;
+
+// Writes 8-bit RGB pixels, row-major, as a PNG file. The image data is stored
+// uncompressed, which keeps the encoder trivial at the cost of file size.
+pub fn write_png(path: &str, width: usize, height: usize, rgb: &[u8]) -> Result<(), String> {
+    // Every row starts with its filter type, 0 for none.
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in rgb.chunks_exact(width * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // zlib stream: header, stored deflate blocks and the Adler-32 checksum.
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(STORED_BLOCK).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push((i + 1 == blocks.len()) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, truecolor, default compression, filter and no
+    // interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut file = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut file, b"IHDR", &header);
+    write_chunk(&mut file, b"IDAT", &zlib);
+    write_chunk(&mut file, b"IEND", &[]);
+    fs::write(path, file).map_err(|e| format!("Failed to write PNG file: {}", e))
+}
+
+fn write_chunk(file: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    file.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = file.len();
+    file.extend_from_slice(kind);
+    file.extend_from_slice(data);
+    let crc = crc32(&file[start..]);
+    file.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,145 @@
+use crate::analysis;
+use crate::cue::CueSheet;
+use crate::decode::Decoded;
+use crate::loudness;
+use crate::spectrum::Spectrum;
+use crate::weighting::{WeightedPeak, Weighting};
+
+// Prints a summary of a decoded file: its format, levels and average spectrum,
+// and the levels of every track if a cue sheet is given.
+pub fn print_report(
+    path: &str,
+    decoded: &Decoded,
+    spectrum: &Spectrum,
+    cue: Option<&CueSheet>,
+    peak_weighting: Option<Weighting>,
+) {
+    let samples = &decoded.samples;
+    let decoded_secs = samples.len() as f32 / decoded.channels as f32 / decoded.sample_rate as f32;
+    println!("File: {}", path);
+    println!(
+        "Format: {} Hz, {} channels{}",
+        decoded.sample_rate,
+        decoded.channels,
+        decoded
+            .sample_format
+            .as_ref()
+            .map_or(String::new(), |format| format!(", {}", format))
+    );
+    if decoded.format_changes > 0 {
+        println!(
+            "Format changes: {} mid-stream, {:.1} s converted to the above",
+            decoded.format_changes, decoded.converted_secs
+        );
+    }
+    if decoded.decoded_packets < decoded.packets {
+        // Assumes packets of equal length, true for nearly every codec.
+        let duration = decoded_secs * decoded.packets as f32 / decoded.decoded_packets as f32;
+        println!("Duration: ~{}", crate::format_duration_millis(duration));
+        println!(
+            "Fast scan: decoded {} of {} packets, levels are estimates",
+            decoded.decoded_packets, decoded.packets
+        );
+    } else {
+        println!("Duration: {}", crate::format_duration_millis(decoded_secs));
+    }
+    // Levels include the input gain, clips are counted in the file as is.
+    let gain = spectrum.input_gain;
+    if gain != 1.0 {
+        println!("Input gain: {:+.1} dB on all levels", analysis::to_db(gain));
+    }
+    println!(
+        "Peak: {:.1} dBFS",
+        analysis::to_db(analysis::peak(samples) * gain)
+    );
+    println!(
+        "RMS: {:.1} dBFS",
+        analysis::to_db(analysis::rms(samples) * gain)
+    );
+    if let Some(weighting) = peak_weighting {
+        let mut detector = WeightedPeak::new(weighting, decoded.channels, decoded.sample_rate);
+        println!(
+            "Peak ({}-weighted): {:.1} dBFS",
+            weighting.label(),
+            analysis::to_db(detector.peak(samples) * gain)
+        );
+    }
+
+    println!("Channels:");
+    for channel in 0..decoded.channels {
+        let channel_samples = || analysis::channel(samples, decoded.channels, channel);
+        println!(
+            "  {:>4} | peak {:>6.1} dBFS | true peak {:>6.1} dBTP | {} clips",
+            analysis::channel_name(channel, decoded.channels),
+            analysis::to_db(analysis::peak(channel_samples()) * gain),
+            analysis::to_db(analysis::true_peak(samples, decoded.channels, channel) * gain),
+            analysis::clip_count(channel_samples(), crate::CLIP_LEVEL)
+        );
+    }
+
+    println!(
+        "Correlation: {:+.2}{}",
+        analysis::correlation(samples, decoded.channels),
+        if analysis::is_mono(decoded.channels) {
+            " (mono)"
+        } else {
+            ""
+        }
+    );
+    match analysis::width_db(samples, decoded.channels) {
+        Some(width) => println!("Width: side {:+.1} dB against mid", width),
+        None => println!("Width: {}", analysis::MONO_NOTE),
+    }
+    if analysis::is_mono(decoded.channels) {
+        println!("Delay: {}", analysis::MONO_NOTE);
+    } else {
+        let (first, second) = (
+            analysis::channel_name(0, decoded.channels),
+            analysis::channel_name(1, decoded.channels),
+        );
+        match analysis::channel_delay_samples(samples, decoded.channels, decoded.sample_rate) {
+            Some((lag, millis)) => println!(
+                "Delay: {} behind {} by {} samples ({:.2} ms)",
+                second, first, lag, millis
+            ),
+            None => println!("Delay: not measurable, the channels are silent or unrelated"),
+        }
+    }
+
+    if let Some(cue) = cue {
+        println!("Tracks:");
+        let channels = decoded.channels;
+        let power: Vec<f32> = loudness::weighted_power(samples, channels, decoded.sample_rate)
+            .into_iter()
+            .map(|p| p * gain * gain)
+            .collect();
+        // With a fast scan the decoded audio is shorter than the file.
+        let frames_per_sec = decoded.sample_rate as f32 * decoded.decoded_packets as f32
+            / decoded.packets.max(1) as f32;
+        for (track, (start, end)) in cue
+            .tracks
+            .iter()
+            .zip(cue.frame_ranges(frames_per_sec, power.len()))
+        {
+            let loudness = loudness::integrated(&power[start..end], decoded.sample_rate)
+                .map_or("  below gate".to_string(), |l| format!("{:>6.1} LUFS", l));
+            println!(
+                "  {} | {} | peak {:>6.1} dBFS | {}",
+                crate::format_duration_millis(track.start_secs),
+                loudness,
+                analysis::to_db(analysis::peak(&samples[start * channels..end * channels]) * gain),
+                track.label()
+            );
+        }
+    }
+
+    println!("Average spectrum ({} window):", spectrum.window.name());
+    let levels = analysis::average_band_levels(spectrum, samples);
+    for (band, db) in levels.iter().enumerate() {
+        let (low_freq, high_freq) = spectrum.band_range(band);
+        println!(
+            "  {:5.0} Hz - {:5.0} Hz | {:>6.1} dB",
+            low_freq, high_freq, db
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use crate::source::BlockProcessor;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaturationCurve {
+    Soft,
+    Hard,
+    Tube,
+}
+
+impl SaturationCurve {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "soft" => Some(SaturationCurve::Soft),
+            "hard" => Some(SaturationCurve::Hard),
+            "tube" => Some(SaturationCurve::Tube),
+            _ => None,
+        }
+    }
+}
+
+pub struct SaturatorProcessor {
+    pub curve: SaturationCurve,
+    pub drive: f32,
+    pub mix: f32,
+}
+
+// Offset applied before the tube curve; it makes the transfer asymmetric and
+// adds even harmonics on top of the odd ones.
+const TUBE_BIAS: f32 = 0.2;
+
+impl SaturatorProcessor {
+    fn shape(&self, input: f32) -> f32 {
+        let driven = input * self.drive;
+        match self.curve {
+            SaturationCurve::Soft => driven.tanh(),
+            SaturationCurve::Hard => driven.clamp(-1.0, 1.0),
+            SaturationCurve::Tube => (driven + TUBE_BIAS).tanh() - TUBE_BIAS.tanh(),
+        }
+    }
+}
+
+impl BlockProcessor for SaturatorProcessor {
+    fn process_sample(&mut self, input: f32) -> Option<f32> {
+        Some((1.0 - self.mix) * input + self.mix * self.shape(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::tests::{harmonic_tone, magnitudes, thd};
+
+    fn thd_after(curve: SaturationCurve, mix: f32) -> f32 {
+        let mut saturator = SaturatorProcessor {
+            curve,
+            drive: 1.5,
+            mix,
+        };
+        let shaped: Vec<f32> = harmonic_tone(4096, 40, &[0.9])
+            .into_iter()
+            .map(|s| saturator.process_sample(s).unwrap())
+            .collect();
+        thd(&magnitudes(&shaped), 40, 9)
+    }
+
+    #[test]
+    fn every_curve_adds_harmonics() {
+        for curve in [
+            SaturationCurve::Soft,
+            SaturationCurve::Hard,
+            SaturationCurve::Tube,
+        ] {
+            assert!(thd_after(curve, 1.0) > 0.01, "{:?}", curve);
+        }
+    }
+
+    #[test]
+    fn dry_mix_adds_nothing() {
+        assert!(thd_after(SaturationCurve::Hard, 0.0) < 1e-4);
+    }
+}
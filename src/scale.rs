@@ -0,0 +1,57 @@
+// How band levels map to bar lengths. This only changes the display; levels
+// and everything exported stay in plain dB.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BarScale {
+    // Bar length proportional to the level in dB.
+    Linear,
+    // Levels above the knee are compressed by the ratio, like a compressor
+    // acting on the bar, so the quiet range below the knee gets more of the
+    // bar and low-level content shows next to loud peaks.
+    Compressed { knee_db: f32, ratio: f32 },
+}
+
+impl BarScale {
+    // `linear` or `compressed[:<knee db>[:<ratio>]]`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.split(':');
+        let name = parts.next().unwrap_or_default();
+        let params: Vec<f32> = parts
+            .map(|p| {
+                p.parse()
+                    .map_err(|_| format!("Invalid bar scale parameter: {}", p))
+            })
+            .collect::<Result<_, _>>()?;
+        match (name, params.as_slice()) {
+            ("linear", []) => Ok(BarScale::Linear),
+            ("compressed", params) if params.len() <= 2 => {
+                let knee_db = params.first().copied().unwrap_or(-40.0);
+                let ratio = params.get(1).copied().unwrap_or(4.0);
+                if ratio < 1.0 {
+                    return Err("Bar scale ratio must be at least 1".to_string());
+                }
+                Ok(BarScale::Compressed { knee_db, ratio })
+            }
+            _ => Err(format!(
+                "Invalid bar scale: {} (expected linear or compressed[:<knee db>[:<ratio>]])",
+                spec
+            )),
+        }
+    }
+
+    // Position of `db` along the bar, 0 at `min_db` and 1 at `max_db`.
+    pub fn fraction(&self, db: f32, min_db: f32, max_db: f32) -> f32 {
+        match *self {
+            BarScale::Linear => (db - min_db) / (max_db - min_db),
+            BarScale::Compressed { knee_db, ratio } => {
+                let compress = |db: f32| {
+                    if db > knee_db {
+                        knee_db + (db - knee_db) / ratio
+                    } else {
+                        db
+                    }
+                };
+                (compress(db) - min_db) / (compress(max_db) - min_db)
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use crate::spectrum::FrameInfo;
+
+// Longest time written lines stay buffered, so little is lost if the
+// process is killed during unattended monitoring.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+// Appends one JSON object per interval of playback to a JSON Lines file.
+// Every entry summarizes the frames since the previous one: band levels and
+// RMS are averaged as power, the peak frequency is the latest detected one
+// and `clip` is set if any frame reached `clip_level`. A write error is
+// reported once and turns the log off, playback carries on.
+pub struct SessionLog {
+    writer: BufWriter<File>,
+    path: String,
+    failed: bool,
+    interval: f32,
+    clip_level: f32,
+    next_entry: f32,
+    band_power: Vec<f32>,
+    rms_power: f32,
+    frames: usize,
+    peak_frequency: Option<f32>,
+    clipped: bool,
+    last_flush: Instant,
+}
+
+impl SessionLog {
+    pub fn new(path: &str, interval: f32, clip_level: f32) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open log {}: {}", path, e))?;
+        Ok(SessionLog {
+            writer: BufWriter::new(file),
+            path: path.to_string(),
+            failed: false,
+            interval,
+            clip_level,
+            next_entry: interval,
+            band_power: Vec::new(),
+            rms_power: 0.0,
+            frames: 0,
+            peak_frequency: None,
+            clipped: false,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, info: &FrameInfo) {
+        if self.failed {
+            return;
+        }
+        if let Err(e) = self.try_record(info) {
+            eprintln!("Failed to write log {}: {}, logging stopped", self.path, e);
+            self.failed = true;
+        }
+    }
+
+    fn try_record(&mut self, info: &FrameInfo) -> std::io::Result<()> {
+        self.band_power.resize(info.band_levels.len(), 0.0);
+        for (sum, db) in self.band_power.iter_mut().zip(info.band_levels) {
+            *sum += 10f32.powf(db / 10.0);
+        }
+        self.rms_power += 10f32.powf(info.rms_db / 10.0);
+        self.frames += 1;
+        self.peak_frequency = info.peak_frequency.or(self.peak_frequency);
+        self.clipped |= info.peak >= self.clip_level;
+
+        // Start over from a seek backwards, otherwise nothing is written
+        // until playback gets back to where it was.
+        if info.timestamp + self.interval < self.next_entry {
+            self.next_entry = info.timestamp + self.interval;
+        }
+        if info.timestamp >= self.next_entry {
+            self.write_entry(info.timestamp)?;
+            // Skip intervals that passed without frames, e.g. while paused.
+            while self.next_entry <= info.timestamp {
+                self.next_entry += self.interval;
+            }
+        }
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.writer.flush()?;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, timestamp: f32) -> std::io::Result<()> {
+        let to_db = |power: f32| 10.0 * (power / self.frames as f32).log10();
+        let bands: Vec<String> = self
+            .band_power
+            .iter()
+            .map(|&power| format!("{:.2}", to_db(power)))
+            .collect();
+        writeln!(
+            self.writer,
+            "{{\"t\":{:.3},\"rms_db\":{:.2},\"peak_hz\":{},\"clip\":{},\"bands\":[{}]}}",
+            timestamp,
+            to_db(self.rms_power),
+            self.peak_frequency
+                .map_or("null".to_string(), |f| format!("{:.1}", f)),
+            self.clipped,
+            bands.join(",")
+        )?;
+        self.band_power.fill(0.0);
+        self.rms_power = 0.0;
+        self.frames = 0;
+        self.peak_frequency = None;
+        self.clipped = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(timestamp: f32) -> FrameInfo<'static> {
+        FrameInfo {
+            timestamp,
+            band_levels: &[-20.0, -40.0],
+            peak_frequency: None,
+            rms_db: -20.0,
+            peak: 0.1,
+            onset: false,
+        }
+    }
+
+    #[test]
+    fn seeking_backwards_restarts_the_entries() {
+        let path =
+            std::env::temp_dir().join(format!("sound_heat_session_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut log = SessionLog::new(path.to_str().unwrap(), 1.0, 1.0).unwrap();
+        for timestamp in [0.5, 1.0, 10.0, 2.0, 2.5, 3.0] {
+            log.record(&frame(timestamp));
+        }
+        drop(log);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let times: Vec<&str> = contents
+            .lines()
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        assert_eq!(times, ["{\"t\":1.000", "{\"t\":10.000", "{\"t\":3.000"]);
+    }
+}
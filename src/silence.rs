@@ -0,0 +1,139 @@
+use crate::analysis;
+use crate::dynamics;
+
+// Length of the blocks whose RMS decides whether audio is silent.
+const BLOCK_SECS: f32 = 0.01;
+// Release of the envelope used for skipping, long enough that pauses between
+// notes or words don't count as silence.
+const SKIP_RELEASE_MS: f32 = 300.0;
+// Silence kept at both ends of a skipped gap, so the jump isn't abrupt.
+const SKIP_MARGIN_SECS: f32 = 0.5;
+// Peak level below which the ends of a file count as silent when trimming,
+// well above dither and tape hiss but below any fade that matters.
+pub const TRIM_THRESHOLD_DB: f32 = -60.0;
+
+// Peak envelope in dB with an instant attack and a one-pole release.
+pub struct EnvelopeFollower {
+    release: f32,
+    envelope_db: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(release_ms: f32, sample_rate: u32) -> Self {
+        EnvelopeFollower {
+            release: dynamics::time_constant(release_ms, sample_rate),
+            envelope_db: -120.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let level_db = 20.0 * (sample.abs() + 1e-10).log10();
+        self.envelope_db = if level_db > self.envelope_db {
+            level_db
+        } else {
+            self.release * self.envelope_db + (1.0 - self.release) * level_db
+        };
+        self.envelope_db
+    }
+}
+
+// Silent stretches of at least `min_gap_secs`, as half-open frame ranges.
+pub fn find_silent_gaps(
+    samples: &[f32],
+    channels: usize,
+    sample_rate: u32,
+    threshold_db: f32,
+    min_gap_secs: f32,
+) -> Vec<(usize, usize)> {
+    let block_frames = ((BLOCK_SECS * sample_rate as f32) as usize).max(1);
+    let total_frames = samples.len() / channels;
+    let min_gap_frames = (min_gap_secs * sample_rate as f32) as usize;
+
+    let mut gaps = Vec::new();
+    let mut gap_start = None;
+    for (i, block) in samples.chunks(block_frames * channels).enumerate() {
+        let silent = analysis::to_db(analysis::rms(block)) < threshold_db;
+        match (silent, gap_start) {
+            (true, None) => gap_start = Some(i * block_frames),
+            (false, Some(start)) => {
+                gaps.push((start, i * block_frames));
+                gap_start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = gap_start {
+        gaps.push((start, total_frames));
+    }
+    gaps.retain(|&(start, end)| end - start >= min_gap_frames);
+    gaps
+}
+
+// Non-silent segments between the gaps, as half-open frame ranges.
+pub fn segments_between(gaps: &[(usize, usize)], total_frames: usize) -> Vec<(usize, usize)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for &(gap_start, gap_end) in gaps {
+        if gap_start > start {
+            segments.push((start, gap_start));
+        }
+        start = gap_end;
+    }
+    if start < total_frames {
+        segments.push((start, total_frames));
+    }
+    segments
+}
+
+// Frame range between the silence at the start and at the end of a file:
+// from the first frame where any channel peaks above `threshold_db` to just
+// after the last one. Empty if the file is silent throughout.
+pub fn trim_silence(samples: &[f32], channels: usize, threshold_db: f32) -> (usize, usize) {
+    let loud = |frame: &[f32]| analysis::to_db(analysis::peak(frame)) >= threshold_db;
+    match samples.chunks_exact(channels).position(loud) {
+        Some(start) => {
+            let end = samples.chunks_exact(channels).rposition(loud).unwrap() + 1;
+            (start, end)
+        }
+        None => (0, 0),
+    }
+}
+
+// Frame ranges to jump over during playback: every stretch of at least
+// `min_gap_secs` where the envelope of the loudest channel stays below
+// `threshold_db`, less a margin at both ends.
+pub fn skip_ranges(
+    samples: &[f32],
+    channels: usize,
+    sample_rate: u32,
+    threshold_db: f32,
+    min_gap_secs: f32,
+) -> Vec<(usize, usize)> {
+    let mut follower = EnvelopeFollower::new(SKIP_RELEASE_MS, sample_rate);
+    let margin = (SKIP_MARGIN_SECS * sample_rate as f32) as usize;
+    let min_gap = ((min_gap_secs * sample_rate as f32) as usize).max(2 * margin + 1);
+
+    let mut ranges = Vec::new();
+    let mut gap_start = None;
+    let total_frames = samples.len() / channels;
+    for (i, frame) in samples.chunks_exact(channels).enumerate() {
+        let silent = follower.process(analysis::peak(frame)) < threshold_db;
+        match (silent, gap_start) {
+            (true, None) => gap_start = Some(i),
+            (false, Some(start)) => {
+                if i - start >= min_gap {
+                    ranges.push((start + margin, i - margin));
+                }
+                gap_start = None;
+            }
+            _ => {}
+        }
+    }
+    // A silent ending is skipped to the end of the file.
+    if let Some(start) = gap_start
+        && total_frames - start >= min_gap
+    {
+        ranges.push((start + margin, total_frames));
+    }
+    ranges
+}
@@ -1,68 +1,174 @@
 use rodio::Source;
+use rodio::source::SeekError;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use crate::chain::ProcessorChain;
+
+// Length of the crossfade between the chain's output and the input when the
+// bypass is switched.
+const BYPASS_FADE_SECS: f32 = 0.02;
+// Frames collected before they are published to a `TapFeed`, so the lock is
+// taken every few milliseconds rather than every frame.
+const PUBLISH_FRAMES: usize = 256;
+
 pub trait BlockProcessor: Send {
-    fn block_size(&self) -> usize;
     fn process_sample(&mut self, sample: f32) -> Option<f32>;
 }
 
+// Processes whole interleaved frames, for effects that mix channels or change
+// the channel count.
+pub trait FrameProcessor: Send {
+    fn process_frame(&mut self, input: &[f32], output: &mut Vec<f32>);
+}
+
 pub struct ProcessedSource {
-    pub samples: Vec<f32>,
+    // Decoded once and shared with the analyzer, so playback and the spectrum
+    // read the same memory. The decoder's Vec moves into the Arc as it is,
+    // where an `Arc<[f32]>` would copy it.
+    pub samples: Arc<Vec<f32>>,
     pub position: usize,
     pub channels: u16,
     pub sample_rate: u32,
-    processors: Vec<Box<dyn BlockProcessor + Send>>,
+    chain: ProcessorChain,
+    output: Vec<f32>,
+    output_position: usize,
+    // Versions of the input playback can switch between, e.g. the files of
+    // an A/B comparison, and the index of the one to play.
+    versions: Vec<Arc<Vec<f32>>>,
+    selected: Arc<AtomicUsize>,
+    // Set to play the input instead of the chain's output. The chain keeps
+    // running, so its state is current when the bypass is lifted.
+    bypass: Arc<AtomicBool>,
+    // Share of the input in the output, moving towards 1 while bypassed.
+    dry_mix: f32,
+    // Where the frames at the chain's tap go, and those not yet sent with
+    // the input frame the first of them belongs to.
+    feed: Option<Arc<TapFeed>>,
+    pending: Vec<f32>,
+    pending_frame: usize,
 }
 
 impl ProcessedSource {
-    pub fn get_samples(&self) -> &Vec<f32> {
+    pub fn get_samples(&self) -> &Arc<Vec<f32>> {
         &self.samples
     }
 
-    pub fn from_source<T>(source: T, processors: Vec<Box<dyn BlockProcessor + Send>>) -> Self
-    where
-        T: rodio::Source<Item = f32>,
-    {
-        let channels = source.channels();
-        let sample_rate = source.sample_rate();
-        let samples: Vec<f32> = source.collect();
+    pub fn from_samples(
+        samples: Arc<Vec<f32>>,
+        channels: u16,
+        sample_rate: u32,
+        chain: ProcessorChain,
+    ) -> Self {
         ProcessedSource {
             samples,
             position: 0,
             channels,
             sample_rate,
-            processors,
+            chain,
+            output: Vec::new(),
+            output_position: 0,
+            versions: Vec::new(),
+            selected: Arc::new(AtomicUsize::new(0)),
+            bypass: Arc::new(AtomicBool::new(false)),
+            dry_mix: 0.0,
+            feed: None,
+            pending: Vec::new(),
+            pending_frame: 0,
         }
     }
+
+    // Publishes the frames at the chain's tap as they are played, keeping
+    // the latest `capacity` of them.
+    pub fn publish_tap(&mut self, capacity: usize) -> Arc<TapFeed> {
+        let feed = Arc::new(TapFeed::new(self.chain.tap_channels(), capacity));
+        self.feed = Some(Arc::clone(&feed));
+        feed
+    }
+
+    fn flush_tap(&mut self) {
+        if let Some(feed) = &self.feed
+            && !self.pending.is_empty()
+        {
+            feed.publish(self.pending_frame, &self.pending);
+            self.pending.clear();
+        }
+    }
+
+    // Switch bypassing the chain while playing. Only takes effect if the
+    // chain keeps the channel count; a latency of the chain isn't made up
+    // for, so the input plays slightly early.
+    pub fn bypass(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.bypass)
+    }
+
+    // Plays `versions[selected]` instead of the samples, switching at the
+    // next frame whenever `selected` changes. The versions need the layout
+    // and length of the samples; the chain runs on across a switch.
+    pub fn play_versions(&mut self, versions: Vec<Arc<Vec<f32>>>, selected: Arc<AtomicUsize>) {
+        self.versions = versions;
+        self.selected = selected;
+    }
 }
 
 impl Iterator for ProcessedSource {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.position >= self.samples.len() {
-            return None;
-        }
-        let mut sample = self.samples[self.position];
-        // let output_sample = self.processor.process(input_sample);
-
-        for proc in self.processors.iter_mut() {
-            if let Some(processed_sample) = proc.process_sample(sample) {
-                sample = processed_sample;
+        if self.output_position >= self.output.len() {
+            let channels = self.channels as usize;
+            if let Some(version) = self.versions.get(self.selected.load(Ordering::Relaxed))
+                && !Arc::ptr_eq(version, &self.samples)
+            {
+                self.samples = Arc::clone(version);
+            }
+            if self.position + channels > self.samples.len() {
+                self.flush_tap();
+                return None;
             }
+            let frame = &self.samples[self.position..self.position + channels];
+            self.chain.process_frame(frame, &mut self.output);
+            let step = 1.0 / (BYPASS_FADE_SECS * self.sample_rate as f32);
+            self.dry_mix = if self.bypass.load(Ordering::Relaxed) {
+                (self.dry_mix + step).min(1.0)
+            } else {
+                (self.dry_mix - step).max(0.0)
+            };
+            if self.dry_mix > 0.0 && self.output.len() == channels {
+                for (output, &input) in self.output.iter_mut().zip(frame) {
+                    *output += (input - *output) * self.dry_mix;
+                }
+            }
+            if self.feed.is_some() {
+                if self.pending.is_empty() {
+                    self.pending_frame = self.position / channels;
+                }
+                self.pending.extend_from_slice(self.chain.tapped());
+                if self.pending.len() >= PUBLISH_FRAMES * self.chain.tap_channels() {
+                    self.flush_tap();
+                }
+            }
+            self.position += channels;
+            self.output_position = 0;
         }
-        self.position += 1;
+        let sample = self.output[self.output_position];
+        self.output_position += 1;
         Some(sample)
     }
 }
 
 impl Source for ProcessedSource {
     fn current_span_len(&self) -> Option<usize> {
-        Some(self.samples.len() - self.position)
+        let frames = (self.samples.len() - self.position) / self.channels as usize;
+        Some(frames * self.chain.output_channels() + self.output.len() - self.output_position)
     }
 
     fn channels(&self) -> u16 {
-        self.channels
+        self.chain.output_channels() as u16
     }
 
     fn sample_rate(&self) -> u32 {
@@ -74,4 +180,259 @@ impl Source for ProcessedSource {
             self.samples.len() as f32 / self.sample_rate as f32 / self.channels as f32,
         ))
     }
+
+    // Jumps to a frame of the input; the chain keeps its state, so filters
+    // ring on briefly across the jump.
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let channels = self.channels as usize;
+        let frame = (pos.as_secs_f64() * self.sample_rate as f64) as usize;
+        self.position = (frame * channels).min(self.samples.len() / channels * channels);
+        self.output.clear();
+        self.output_position = 0;
+        self.pending.clear();
+        if let Some(feed) = &self.feed {
+            feed.restart(self.position / channels);
+        }
+        Ok(())
+    }
+}
+
+// The signal at the chain's tap as a `ProcessedSource` plays it, for the
+// analyzer to read instead of rendering the whole track through a second
+// chain. Frames are numbered by the input frame they were made from and the
+// latest `capacity` of them are kept.
+pub struct TapFeed {
+    channels: usize,
+    capacity: usize,
+    ring: Mutex<TapRing>,
+}
+
+struct TapRing {
+    samples: VecDeque<f32>,
+    // Number of the frame after the last one held.
+    end: usize,
+}
+
+impl TapFeed {
+    fn new(channels: usize, capacity: usize) -> Self {
+        TapFeed {
+            channels,
+            capacity,
+            ring: Mutex::new(TapRing {
+                samples: VecDeque::with_capacity(capacity * channels),
+                end: 0,
+            }),
+        }
+    }
+
+    // Appends interleaved frames starting at frame `first`. Frames that
+    // don't follow on from those held, as after a seek, replace them.
+    fn publish(&self, first: usize, samples: &[f32]) {
+        let mut ring = self.ring.lock().unwrap();
+        if first != ring.end {
+            ring.samples.clear();
+        }
+        ring.samples.extend(samples);
+        let excess = ring
+            .samples
+            .len()
+            .saturating_sub(self.capacity * self.channels);
+        ring.samples.drain(..excess);
+        ring.end = first + samples.len() / self.channels;
+    }
+
+    // Drops every frame held, playback continues at frame `frame`.
+    fn restart(&self, frame: usize) {
+        let mut ring = self.ring.lock().unwrap();
+        ring.samples.clear();
+        ring.end = frame;
+    }
+
+    // Replaces `out` with the interleaved samples `range` of the tap, as
+    // positions of the interleaved signal. Frames no longer held are left
+    // out of the front. False, leaving `out` empty, until all of them have
+    // been played.
+    pub fn read_into(&self, range: Range<usize>, out: &mut Vec<f32>) -> bool {
+        out.clear();
+        let ring = self.ring.lock().unwrap();
+        let held = ring.samples.len();
+        let end = ring.end * self.channels;
+        if range.end > end {
+            return false;
+        }
+        let start = range.start.max(end - held);
+        if start < range.end {
+            let offset = held - (end - start);
+            out.extend(ring.samples.range(offset..offset + (range.end - start)));
+        }
+        true
+    }
+}
+
+// Interleaved samples, or the error that ended a stream.
+pub type Chunk = Result<Vec<f32>, String>;
+
+// Plays audio that is decoded while it plays, e.g. a stream from a URL,
+// through the chain. Chunks arrive from a decoding thread; while the next
+// one is late, playback is silent. Every chunk is passed on to `played`
+// once it has been handed to the output, so the analyzer follows what is
+// heard. Ends with the decoder, passing on its error if it failed.
+pub struct StreamingSource {
+    chunks: Receiver<Chunk>,
+    played: Sender<Chunk>,
+    channels: usize,
+    sample_rate: u32,
+    chain: ProcessorChain,
+    frame: Vec<f32>,
+    output: Vec<f32>,
+    output_position: usize,
+    // Whether `output` is filler for a late chunk, which isn't passed on.
+    waiting: bool,
+}
+
+impl StreamingSource {
+    pub fn new(
+        chunks: Receiver<Chunk>,
+        played: Sender<Chunk>,
+        channels: usize,
+        sample_rate: u32,
+        chain: ProcessorChain,
+    ) -> Self {
+        StreamingSource {
+            chunks,
+            played,
+            channels,
+            sample_rate,
+            chain,
+            frame: Vec::new(),
+            output: Vec::new(),
+            output_position: 0,
+            waiting: false,
+        }
+    }
+}
+
+impl Iterator for StreamingSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.output_position >= self.output.len() {
+            if !self.waiting && !self.output.is_empty() {
+                // The analyzer is only gone while shutting down.
+                let _ = self.played.send(Ok(self.output.clone()));
+            }
+            self.output.clear();
+            self.output_position = 0;
+            match self.chunks.try_recv() {
+                Ok(Ok(chunk)) => {
+                    self.waiting = false;
+                    for frame in chunk.chunks_exact(self.channels) {
+                        self.chain.process_frame(frame, &mut self.frame);
+                        self.output.extend_from_slice(&self.frame);
+                    }
+                }
+                Ok(Err(message)) => {
+                    let _ = self.played.send(Err(message));
+                    return None;
+                }
+                Err(TryRecvError::Empty) => {
+                    self.waiting = true;
+                    self.output.resize(self.chain.output_channels(), 0.0);
+                }
+                Err(TryRecvError::Disconnected) => return None,
+            }
+        }
+        let sample = self.output[self.output_position];
+        self.output_position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for StreamingSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.chain.output_channels() as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn streaming_source_plays_and_passes_on_chunks() {
+        let (sender, chunks) = mpsc::sync_channel(4);
+        let (played_sender, played) = mpsc::channel();
+        let chain = ProcessorChain::parse("gain:-6.0206", 1, 48000).unwrap();
+        let mut source = StreamingSource::new(chunks, played_sender, 1, 48000, chain);
+
+        // Nothing decoded yet plays as silence and isn't passed on.
+        assert_eq!(source.next(), Some(0.0));
+        sender.send(Ok(vec![1.0, -1.0])).unwrap();
+        sender.send(Ok(vec![0.5])).unwrap();
+        drop(sender);
+        let output: Vec<f32> = source.collect();
+        let expected = [0.5, -0.5, 0.25];
+        assert_eq!(output.len(), expected.len());
+        for (got, want) in output.iter().zip(expected) {
+            assert!((got - want).abs() < 1e-4);
+        }
+        let passed: Vec<Vec<f32>> = played.try_iter().map(Result::unwrap).collect();
+        assert_eq!(passed.len(), 2);
+        assert_eq!(passed.concat(), output);
+    }
+
+    #[test]
+    fn streaming_source_ends_with_the_decoder_error() {
+        let (sender, chunks) = mpsc::sync_channel(4);
+        let (played_sender, played) = mpsc::channel();
+        let chain = ProcessorChain::parse("", 2, 48000).unwrap();
+        let mut source = StreamingSource::new(chunks, played_sender, 2, 48000, chain);
+        sender.send(Err("broken".to_string())).unwrap();
+        assert_eq!(source.next(), None);
+        assert_eq!(played.try_recv().unwrap(), Err("broken".to_string()));
+    }
+
+    #[test]
+    fn processed_source_publishes_the_tap() {
+        let input: Vec<f32> = (0..1000).map(|n| n as f32).collect();
+        let mut chain = ProcessorChain::parse("gain:-6.0206,gain:-6.0206", 1, 48000).unwrap();
+        chain.tap = 1;
+        let mut source = ProcessedSource::from_samples(Arc::new(input), 1, 48000, chain);
+        let feed = source.publish_tap(300);
+
+        let mut out = Vec::new();
+        // Only what has been played is published.
+        assert!(!feed.read_into(0..10, &mut out));
+        let output: Vec<f32> = source.by_ref().take(700).collect();
+        assert!((output[100] - 25.0).abs() < 0.01);
+        // Published every 256 frames, the last 300 of them kept.
+        assert!(!feed.read_into(600..610, &mut out));
+        assert!(feed.read_into(300..310, &mut out));
+        assert!((out[0] - 150.0).abs() < 0.01);
+        assert!(feed.read_into(0..512, &mut out));
+        assert_eq!(out.len(), 300);
+        assert!((out[0] - 106.0).abs() < 0.01);
+
+        source
+            .try_seek(Duration::from_secs_f32(100.0 / 48000.0))
+            .unwrap();
+        assert!(!feed.read_into(100..110, &mut out));
+        assert!(out.is_empty());
+        source.by_ref().for_each(drop);
+        assert!(feed.read_into(990..1000, &mut out));
+        assert!((out[9] - 499.5).abs() < 0.01);
+    }
 }
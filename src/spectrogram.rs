@@ -0,0 +1,100 @@
+use crate::analysis;
+use crate::spectrum::Spectrum;
+
+// Where a pixel row reads its power from the FFT bins.
+enum RowSource {
+    // Mean power of the bins inside the row's frequency range.
+    Bins(std::ops::Range<usize>),
+    // Rows narrower than a bin interpolate between the two bins around
+    // their centre.
+    Between(usize, f32),
+}
+
+// Spectrogram of interleaved samples resampled to `width` columns over time
+// and `height` rows over log frequency, from the spectrum's `min_freq` to
+// its `max_freq` like the bands. Every hop of `hop_size` frames is one FFT
+// frame with the spectrum's window and channel mode. Where several frames or
+// bins fall on a pixel their power is averaged; where a pixel lies between
+// them it is interpolated, so the image holds all the detail of its size and
+// no more. Returns the level in dB of every column, lowest row first.
+pub fn spectrogram(
+    spectrum: &Spectrum,
+    samples: &[f32],
+    hop_size: usize,
+    width: usize,
+    height: usize,
+) -> Vec<Vec<f32>> {
+    let channels = spectrum.channels;
+    let bin_width = spectrum.sample_rate as f32 / spectrum.fft_len() as f32;
+    let nyquist = spectrum.fft_len() / 2;
+    let ratio = spectrum.max_freq / spectrum.min_freq;
+    let rows: Vec<RowSource> = (0..height)
+        .map(|row| {
+            let low = spectrum.min_freq * ratio.powf(row as f32 / height as f32);
+            let high = spectrum.min_freq * ratio.powf((row + 1) as f32 / height as f32);
+            let first = ((low / bin_width).ceil() as usize).clamp(1, nyquist);
+            let last = ((high / bin_width).ceil() as usize).clamp(1, nyquist);
+            if first < last {
+                RowSource::Bins(first..last)
+            } else {
+                let position = ((low * high).sqrt() / bin_width).min((nyquist - 1) as f32);
+                RowSource::Between(position as usize, position.fract())
+            }
+        })
+        .collect();
+
+    // Power of every row in every frame.
+    let frames: Vec<Vec<f32>> =
+        analysis::windows(samples, spectrum.fft_size * channels, hop_size * channels)
+            .map(|frame| {
+                let power: Vec<f32> = spectrum
+                    .frame_magnitudes(frame)
+                    .iter()
+                    .map(|m| m * m)
+                    .collect();
+                rows.iter()
+                    .map(|source| match source {
+                        RowSource::Bins(bins) => {
+                            power[bins.clone()].iter().sum::<f32>() / bins.len() as f32
+                        }
+                        RowSource::Between(bin, t) => {
+                            power[*bin] + (power[bin + 1] - power[*bin]) * t
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+    if frames.is_empty() {
+        return vec![vec![analysis::to_db(0.0); height]; width];
+    }
+
+    // Frame `i` is placed at the centre of its window.
+    let total_frames = samples.len() / channels;
+    let frame_at = |column: f32| {
+        (column / width as f32 * total_frames as f32 - spectrum.fft_size as f32 / 2.0)
+            / hop_size as f32
+    };
+    (0..width)
+        .map(|column| {
+            let start = frame_at(column as f32).ceil().max(0.0) as usize;
+            let end = (frame_at(column as f32 + 1.0).ceil().max(0.0) as usize).min(frames.len());
+            let power: Vec<f32> = if start < end {
+                (0..height)
+                    .map(|row| {
+                        frames[start..end].iter().map(|f| f[row]).sum::<f32>()
+                            / (end - start) as f32
+                    })
+                    .collect()
+            } else {
+                let position = frame_at(column as f32 + 0.5).clamp(0.0, (frames.len() - 1) as f32);
+                let before = position as usize;
+                let after = (before + 1).min(frames.len() - 1);
+                let t = position.fract();
+                (0..height)
+                    .map(|row| frames[before][row] + (frames[after][row] - frames[before][row]) * t)
+                    .collect()
+            };
+            power.iter().map(|p| 10.0 * (p + 1e-20).log10()).collect()
+        })
+        .collect()
+}
@@ -1,20 +1,134 @@
 use std::io::Stdout;
 
-use crossterm::execute;
+use crossterm::style::{Color, Print, SetForegroundColor};
+use crossterm::terminal::{Clear, ClearType};
+use crossterm::{execute, queue};
+
+use crate::analysis::{self, ChannelMode};
+use crate::onset::OnsetDetector;
+use crate::scale::BarScale;
+use crate::target;
+use crate::tempo::BeatGrid;
+use crate::theme::{self, Theme};
+use crate::window::Window;
 use rustfft::{FftPlanner, num_complex::Complex};
 use std::io::Write;
 
+// Summary of one analyzed frame, handed to the `on_frame` callback.
+pub struct FrameInfo<'a> {
+    // Position of the newest sample in the frame, in seconds.
+    pub timestamp: f32,
+    pub band_levels: &'a [f32],
+    // Frequency of the strongest bin, `None` for silent frames.
+    pub peak_frequency: Option<f32>,
+    pub rms_db: f32,
+    // Largest absolute sample of the window, over all channels.
+    pub peak: f32,
+    // Whether the onset detector fired on this frame.
+    pub onset: bool,
+}
+
+pub type FrameCallback = Box<dyn FnMut(&FrameInfo)>;
+
 pub struct Spectrum {
     pub bands: usize,
     pub min_db: f32,
     pub max_db: f32,
     pub smooth_factor: f32,
     pub smoothed_by_band: Vec<f32>,
+    // Smoothed levels before the latest frame, the start of interpolated bars.
+    previous_by_band: Vec<f32>,
+    pub smooth_by_band: Vec<f32>,
     pub fft_size: usize,
+    // Windows are zero-padded to `fft_size * zero_pad_factor` points. The
+    // denser bins interpolate the spectrum, which smooths bars and peak
+    // positions, but add no real resolution: that is set by `fft_size`.
+    pub zero_pad_factor: usize,
     pub fft: std::sync::Arc<dyn rustfft::Fft<f32>>,
     pub sample_rate: u32,
+    // Layout of the samples passed in and how they are collapsed.
+    pub channels: usize,
+    pub channel_mode: ChannelMode,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    // Per-band levels that can be drawn as markers over the bars, e.g. of
+    // reference tracks, and the index of the one drawn.
+    pub overlays: Vec<Vec<f32>>,
+    pub overlay: Option<usize>,
+    pub peak_freq_by_band: Vec<f32>,
+    pub show_peaks: bool,
+    // Share of the total energy in every band, in percent.
+    pub energy_share_by_band: Vec<f32>,
+    pub show_energy_share: bool,
+    // Color every bar by the octave of its centre frequency instead of by
+    // its length.
+    pub color_by_octave: bool,
+    // Draw a filled curve over frequency across the width of the terminal,
+    // one row per band of height, instead of a bar per band.
+    pub draw_curve: bool,
+    // Draw a faint cell for bands whose bar is empty, so silent bands still
+    // show a baseline. Bands without any FFT bin, too narrow for the
+    // resolution or above Nyquist, stay blank.
+    pub floor_shading: bool,
+    // Terminal row of the lowest band, leaving room for anything drawn above.
+    pub top_row: u16,
+    pub theme: Theme,
+    // Frames with an RMS below this level count as silence.
+    pub silence_db: f32,
+    // Linear gain applied to the samples before analysis, shifting every
+    // reported level but not playback.
+    pub input_gain: f32,
+    pub window: Window,
+    // Divides out the window's noise bandwidth, so broadband noise reads at
+    // its true level while tones read slightly low.
+    pub enbw_correction: bool,
+    // Count the DC bin in the lowest band, see `band_bins`.
+    pub include_dc: bool,
+    pub bar_scale: BarScale,
+    // Seconds the loudest recent level of every band is held for the
+    // readout before it falls back, `None` to hide the readout.
+    pub hold_secs: Option<f32>,
+    held_by_band: Vec<f32>,
+    // Timestamp at which every held level was reached.
+    held_since: Vec<f32>,
+    last_timestamp: f32,
+    // Target level of every band, e.g. from a house curve; bands then
+    // average their power over all non-silent frames to compare with it.
+    pub target: Option<Vec<f32>>,
+    // Draw the deviation of the average from the target instead of bars.
+    pub show_target: bool,
+    power_sum_by_band: Vec<f32>,
+    averaged_frames: usize,
+    // Beats of the track; with `show_beat_average` the bars hold the
+    // average of the last whole beat, so they change once per beat.
+    pub beat_grid: Option<BeatGrid>,
+    pub show_beat_average: bool,
+    // Bar levels captured by `take_snapshot`; while set, the bars show how
+    // far every band has moved since, e.g. after an EQ change elsewhere.
+    pub snapshot: Option<Vec<f32>>,
+    current_beat: i64,
+    beat_power_by_band: Vec<f32>,
+    beat_frames: usize,
+    beat_average_by_band: Option<Vec<f32>>,
+    frame_callbacks: Vec<FrameCallback>,
+    onsets: OnsetDetector,
 }
 
+// Smoothing used while silent, so bars empty quickly instead of ghosting.
+const SILENCE_SMOOTH_FACTOR: f32 = 0.3;
+
+// Rate at which a held level falls once its hold time is over.
+const HOLD_RELEASE_DB_PER_SEC: f32 = 20.0;
+
+// Length of a bar at `max_db`, in columns.
+const BAR_COLUMNS: usize = 150;
+// Cells filled by 0 to 8 eighths, from the bottom up.
+const CURVE_GLYPHS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+// Baseline of an empty bar with `floor_shading`.
+const FLOOR_GLYPH: char = '░';
+// Change drawn at the full half width of the difference view.
+const DIFFERENCE_RANGE_DB: f32 = 24.0;
+
 impl Spectrum {
     pub fn new(
         bands: usize,
@@ -30,80 +144,665 @@ impl Spectrum {
             max_db,
             smooth_factor,
             smoothed_by_band: vec![min_db; bands],
+            previous_by_band: vec![min_db; bands],
+            smooth_by_band: vec![smooth_factor; bands],
             fft_size,
+            zero_pad_factor: 1,
             fft: FftPlanner::<f32>::new().plan_fft_forward(fft_size),
             sample_rate,
+            channels: 1,
+            channel_mode: ChannelMode::Mid,
+            min_freq: 20.0,
+            // Keep the display on the audible range for high-res files.
+            max_freq: (sample_rate as f32 / 2.0).min(20000.0),
+            overlays: Vec::new(),
+            overlay: None,
+            peak_freq_by_band: vec![0.0; bands],
+            show_peaks: false,
+            energy_share_by_band: vec![0.0; bands],
+            show_energy_share: false,
+            color_by_octave: false,
+            draw_curve: false,
+            floor_shading: false,
+            top_row: 0,
+            theme: Theme::default(),
+            silence_db: -60.0,
+            input_gain: 1.0,
+            window: Window::Hann,
+            enbw_correction: false,
+            include_dc: false,
+            bar_scale: BarScale::Linear,
+            hold_secs: None,
+            held_by_band: vec![min_db; bands],
+            held_since: vec![0.0; bands],
+            last_timestamp: 0.0,
+            target: None,
+            show_target: false,
+            power_sum_by_band: vec![0.0; bands],
+            averaged_frames: 0,
+            beat_grid: None,
+            show_beat_average: false,
+            snapshot: None,
+            current_beat: i64::MIN,
+            beat_power_by_band: vec![0.0; bands],
+            beat_frames: 0,
+            beat_average_by_band: None,
+            frame_callbacks: Vec::new(),
+            onsets: OnsetDetector::new(16),
         }
     }
 
-    pub fn render(&mut self, samples: &[f32], stdout: &mut Stdout) {
+    // High bands flicker much more than low ones, so smooth them harder: the
+    // factor rises linearly from `smooth_factor` at the lowest band to halfway
+    // between it and 1.0 at the highest.
+    pub fn use_frequency_dependent_smoothing(&mut self) {
+        let top = self.smooth_factor + (1.0 - self.smooth_factor) * 0.5;
+        let last = (self.bands - 1).max(1) as f32;
+        self.smooth_by_band = (0..self.bands)
+            .map(|band| self.smooth_factor + (top - self.smooth_factor) * band as f32 / last)
+            .collect();
+    }
+
+    pub fn set_zero_pad_factor(&mut self, factor: usize) {
+        self.zero_pad_factor = factor.max(1);
+        self.fft = FftPlanner::<f32>::new().plan_fft_forward(self.fft_len());
+    }
+
+    // Changes the window length while running, trading frequency against
+    // time resolution. The bars carry on from their current levels.
+    pub fn set_fft_size(&mut self, fft_size: usize) {
+        self.fft_size = fft_size;
+        self.fft = FftPlanner::<f32>::new().plan_fft_forward(self.fft_len());
+    }
+
+    // Number of FFT points, and so of bins across the full sample rate.
+    pub fn fft_len(&self) -> usize {
+        self.fft_size * self.zero_pad_factor
+    }
+
+    pub fn band_range(&self, band: usize) -> (f32, f32) {
+        let log_min = self.min_freq.ln();
+        let log_max = self.max_freq.ln();
+        let log_low = log_min + (log_max - log_min) * (band as f32) / (self.bands as f32);
+        let log_high = log_min + (log_max - log_min) * ((band + 1) as f32) / (self.bands as f32);
+        (log_low.exp(), log_high.exp())
+    }
+
+    pub fn magnitudes(&self, samples: &[f32]) -> Vec<f32> {
+        self.channel_magnitudes(samples.iter())
+    }
+
+    // Magnitude spectrum of samples read in place, e.g. one `analysis::channel`.
+    fn channel_magnitudes<'a>(&self, samples: impl ExactSizeIterator<Item = &'a f32>) -> Vec<f32> {
+        let len = samples.len();
         let mut buffer: Vec<Complex<f32>> = samples
-            .iter()
             .enumerate()
-            .map(|(i, &s)| {
-                let hann = 0.5
-                    * (1.0
-                        - (2.0 * std::f32::consts::PI * i as f32 / (samples.len() as f32 - 1.0))
-                            .cos());
-                Complex {
-                    re: s * hann,
-                    im: 0.0,
-                }
+            .map(|(i, &s)| Complex {
+                re: s * self.window.coefficient(i, len),
+                im: 0.0,
             })
             .collect();
+        buffer.resize(self.fft_len(), Complex { re: 0.0, im: 0.0 });
         self.fft.process(&mut buffer);
 
-        let spectrum: Vec<f32> = buffer
-            .iter()
-            .map(|c| c.norm() / self.fft_size as f32)
+        // Normalized by the window length, so padding doesn't change levels,
+        // and so tones read the same with every window as with Hann.
+        let mut scale = self.input_gain * Window::Hann.coherent_gain()
+            / self.window.coherent_gain()
+            / self.fft_size as f32;
+        if self.enbw_correction {
+            scale /= self.window.enbw().sqrt();
+        }
+        buffer.iter().map(|c| c.norm() * scale).collect()
+    }
+
+    // Magnitude spectrum of a window of interleaved samples, collapsed with
+    // the channel mode.
+    pub fn frame_magnitudes(&self, samples: &[f32]) -> Vec<f32> {
+        if self.channel_mode != ChannelMode::Max || self.channels == 1 {
+            return self.magnitudes(&analysis::downmix(
+                samples,
+                self.channels,
+                self.channel_mode,
+            ));
+        }
+        let mut max = self.channel_magnitudes(analysis::channel(samples, self.channels, 0));
+        for channel in 1..self.channels {
+            let spectrum =
+                self.channel_magnitudes(analysis::channel(samples, self.channels, channel));
+            for (max, value) in max.iter_mut().zip(spectrum) {
+                *max = max.max(value);
+            }
+        }
+        max
+    }
+
+    // FFT bins that fall inside a band.
+    //
+    // The DC bin (0) and the Nyquist bin (half the FFT length) are left out.
+    // Bin 0 is no frequency but the window's mean, so any DC offset lands
+    // there in full and would inflate the lowest band, which reaches it at
+    // high sample rates or with a low `min_freq`. Both are also the only
+    // bins without a mirror image at negative frequencies, so their
+    // magnitudes read 6 dB apart from a tone's in any other bin. With
+    // `include_dc` the lowest band starts at bin 0 regardless.
+    pub fn band_bins(&self, band: usize) -> std::ops::Range<usize> {
+        let (low_freq, high_freq) = self.band_range(band);
+        let low_bin =
+            ((low_freq / self.sample_rate as f32) * self.fft_len() as f32).floor() as usize;
+        let high_bin =
+            ((high_freq / self.sample_rate as f32) * self.fft_len() as f32).ceil() as usize;
+        let first = if self.include_dc && band == 0 { 0 } else { 1 };
+        let nyquist = self.fft_len() / 2;
+        low_bin.clamp(first, nyquist)..high_bin.clamp(first, nyquist)
+    }
+
+    // Unsmoothed level of every band in dB.
+    pub fn band_levels(&self, samples: &[f32]) -> Vec<f32> {
+        self.levels_from_magnitudes(&self.frame_magnitudes(samples))
+    }
+
+    pub fn levels_from_magnitudes(&self, spectrum: &[f32]) -> Vec<f32> {
+        (0..self.bands)
+            .map(|band| {
+                let band_bins = &spectrum[self.band_bins(band)];
+                let avg = if !band_bins.is_empty() {
+                    band_bins.iter().sum::<f32>() / band_bins.len() as f32
+                } else {
+                    0.0
+                };
+                let epsilon = 1e-10;
+                20.0 * (avg + epsilon).log10()
+            })
+            .collect()
+    }
+
+    // Frequency of the strongest bin in every band, showing where inside a
+    // broad band the energy sits.
+    pub fn peak_frequencies(&self, spectrum: &[f32]) -> Vec<f32> {
+        let bin_width = self.sample_rate as f32 / self.fft_len() as f32;
+        (0..self.bands)
+            .map(|band| {
+                let bins = self.band_bins(band);
+                let start = bins.start;
+                spectrum[bins]
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.total_cmp(b.1))
+                    .map_or(0.0, |(i, _)| (start + i) as f32 * bin_width)
+            })
+            .collect()
+    }
+
+    // Linear energy of every band as a percentage of the energy summed over
+    // all bands, which shows the tonal balance independent of the level.
+    pub fn energy_shares(&self, spectrum: &[f32]) -> Vec<f32> {
+        let energies: Vec<f32> = (0..self.bands)
+            .map(|band| spectrum[self.band_bins(band)].iter().map(|m| m * m).sum())
             .collect();
+        let total: f32 = energies.iter().sum();
+        if total <= 0.0 {
+            return vec![0.0; self.bands];
+        }
+        energies.iter().map(|e| e / total * 100.0).collect()
+    }
+
+    // Registers a callback invoked for every analyzed frame. Nothing extra is
+    // computed while no callback is set.
+    pub fn on_frame(&mut self, callback: impl FnMut(&FrameInfo) + 'static) {
+        self.frame_callbacks.push(Box::new(callback));
+    }
+
+    // Sets the smoothed bars straight to the levels of a frame, e.g. so the
+    // display doesn't ramp up from silence at the start of playback. Returns
+    // the raw levels.
+    pub fn seed(&mut self, samples: &[f32]) -> Vec<f32> {
+        let spectrum = self.frame_magnitudes(samples);
+        let levels = self.levels_from_magnitudes(&spectrum);
+        self.peak_freq_by_band = self.peak_frequencies(&spectrum);
+        self.energy_share_by_band = self.energy_shares(&spectrum);
+        self.smoothed_by_band = levels.iter().map(|db| db.max(self.min_db)).collect();
+        self.previous_by_band = self.smoothed_by_band.clone();
+        levels
+    }
+
+    // Updates the smoothed bars with a window of `fft_size` interleaved frames
+    // and returns its raw levels.
+    // Levels are floored at `min_db` before smoothing; otherwise near-silent
+    // bins at the epsilon floor drag the average far below the visible range
+    // and bars take a long, uneven time to recover.
+    pub fn analyze(&mut self, samples: &[f32], timestamp: f32) -> Vec<f32> {
+        let spectrum = self.frame_magnitudes(samples);
+        let levels = self.levels_from_magnitudes(&spectrum);
+        self.peak_freq_by_band = self.peak_frequencies(&spectrum);
+        self.energy_share_by_band = self.energy_shares(&spectrum);
+        let rms_db = analysis::to_db(
+            analysis::rms(&analysis::downmix(
+                samples,
+                self.channels,
+                self.channel_mode,
+            )) * self.input_gain,
+        );
+        let silent = rms_db < self.silence_db;
+        self.previous_by_band = self.smoothed_by_band.clone();
+        for ((smoothed, &factor), &db) in self
+            .smoothed_by_band
+            .iter_mut()
+            .zip(&self.smooth_by_band)
+            .zip(&levels)
+        {
+            let (factor, db) = if silent {
+                (factor.min(SILENCE_SMOOTH_FACTOR), self.min_db)
+            } else {
+                (factor, db.max(self.min_db))
+            };
+            *smoothed = factor * *smoothed + (1.0 - factor) * db;
+        }
+        if let Some(hold_secs) = self.hold_secs {
+            self.update_held(&levels, timestamp, hold_secs);
+        }
+        if self.target.is_some() && !silent {
+            for (sum, &db) in self.power_sum_by_band.iter_mut().zip(&levels) {
+                *sum += 10f32.powf(db / 10.0);
+            }
+            self.averaged_frames += 1;
+        }
+        if let Some(grid) = self.beat_grid {
+            self.update_beat_average(&levels, grid.beat_at(timestamp));
+            if self.show_beat_average
+                && let Some(average) = &self.beat_average_by_band
+            {
+                self.smoothed_by_band = average.clone();
+            }
+        }
 
-        let min_freq: f32 = 20.0;
-        let max_freq: f32 = self.sample_rate as f32 / 2.0;
-        let log_min = min_freq.ln();
-        let log_max = max_freq.ln();
-
-        for band in 0..self.bands {
-            let log_low = log_min + (log_max - log_min) * (band as f32) / (self.bands as f32);
-            let log_high =
-                log_min + (log_max - log_min) * ((band + 1) as f32) / (self.bands as f32);
-            let low_freq = log_low.exp();
-            let high_freq = log_high.exp();
-
-            let low_bin =
-                ((low_freq / self.sample_rate as f32) * self.fft_size as f32).floor() as usize;
-            let high_bin =
-                ((high_freq / self.sample_rate as f32) * self.fft_size as f32).ceil() as usize;
-            let band_bins = &spectrum[low_bin..high_bin.min(spectrum.len())];
-            let avg = if !band_bins.is_empty() {
-                band_bins.iter().sum::<f32>() / band_bins.len() as f32
+        if !self.frame_callbacks.is_empty() {
+            let peak_frequency = if silent {
+                None
             } else {
-                0.0
+                Some(self.peak_frequency(&spectrum))
+            };
+            let (_, onset) = self.onsets.process(&levels, self.min_db);
+            let info = FrameInfo {
+                timestamp,
+                band_levels: &levels,
+                peak_frequency,
+                rms_db,
+                peak: analysis::peak(samples) * self.input_gain,
+                onset,
             };
-            let epsilon = 1e-10;
-            let db = 20.0 * (avg + epsilon).log10();
-            self.smoothed_by_band[band] =
-                self.smooth_factor * self.smoothed_by_band[band] + (1.0 - self.smooth_factor) * db;
-            let bar_len = (((self.smoothed_by_band[band] - self.min_db)
-                / (self.max_db - self.min_db))
-                * 150.0)
-                .max(0.0) as usize;
-            let bar = "█".repeat(bar_len);
+            for callback in self.frame_callbacks.iter_mut() {
+                callback(&info);
+            }
+        }
+        levels
+    }
+
+    // Adds a frame to the average of its beat. When the next beat starts the
+    // finished one is kept for display; a jump, e.g. from seeking, drops the
+    // partial beat instead.
+    fn update_beat_average(&mut self, levels: &[f32], beat: i64) {
+        if beat != self.current_beat {
+            if beat == self.current_beat.wrapping_add(1) && self.beat_frames > 0 {
+                self.beat_average_by_band = Some(
+                    self.beat_power_by_band
+                        .iter()
+                        .map(|&sum| 10.0 * (sum / self.beat_frames as f32).log10())
+                        .collect(),
+                );
+            }
+            self.current_beat = beat;
+            self.beat_power_by_band.fill(0.0);
+            self.beat_frames = 0;
+        }
+        for (sum, &db) in self.beat_power_by_band.iter_mut().zip(levels) {
+            *sum += 10f32.powf(db.max(self.min_db) / 10.0);
+        }
+        self.beat_frames += 1;
+    }
+
+    // Drops the held levels, so after a seek or scrub the holds start over
+    // from the new position instead of keeping peaks from elsewhere.
+    pub fn reset_hold(&mut self) {
+        self.held_by_band.fill(self.min_db);
+        self.held_since.fill(0.0);
+        self.last_timestamp = 0.0;
+    }
+
+    // Raises every held level to a louder raw one, or lets it fall once it
+    // is older than `hold_secs`, but never below the current level.
+    fn update_held(&mut self, levels: &[f32], timestamp: f32, hold_secs: f32) {
+        // After a reset the first timestamp can be far from the last one.
+        let elapsed = (timestamp - self.last_timestamp).max(0.0);
+        self.last_timestamp = timestamp;
+        for ((held, since), &db) in self
+            .held_by_band
+            .iter_mut()
+            .zip(self.held_since.iter_mut())
+            .zip(levels)
+        {
+            let db = db.max(self.min_db);
+            if db >= *held {
+                *held = db;
+                *since = timestamp;
+            } else if timestamp - *since > hold_secs {
+                *held = (*held - HOLD_RELEASE_DB_PER_SEC * elapsed).max(db);
+            }
+        }
+    }
+
+    // Frequency of the strongest bin within the displayed range.
+    pub fn peak_frequency(&self, spectrum: &[f32]) -> f32 {
+        let bin_width = self.sample_rate as f32 / self.fft_len() as f32;
+        let low_bin = self.band_bins(0).start;
+        let high_bin = self.band_bins(self.bands - 1).end;
+        spectrum[low_bin..high_bin]
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0.0, |(i, _)| (low_bin + i) as f32 * bin_width)
+    }
+
+    pub fn render(&mut self, samples: &[f32], timestamp: f32, stdout: &mut Stdout) {
+        let levels = self.analyze(samples, timestamp);
+        self.draw(&levels, stdout);
+    }
+
+    pub fn draw(&self, levels: &[f32], stdout: &mut Stdout) {
+        self.draw_bars(levels, &self.smoothed_by_band, stdout);
+    }
+
+    // Draws the bars `fraction` of the way from their heights before the
+    // latest frame to the current ones, for frames in between analyses.
+    pub fn draw_interpolated(&self, levels: &[f32], fraction: f32, stdout: &mut Stdout) {
+        let bars: Vec<f32> = self
+            .previous_by_band
+            .iter()
+            .zip(&self.smoothed_by_band)
+            .map(|(&from, &to)| from + (to - from) * fraction)
+            .collect();
+        self.draw_bars(levels, &bars, stdout);
+    }
+
+    fn overlay_levels(&self) -> Option<&[f32]> {
+        self.overlay.map(|index| self.overlays[index].as_slice())
+    }
+
+    pub fn take_snapshot(&mut self) {
+        self.snapshot = Some(self.smoothed_by_band.clone());
+    }
+
+    fn draw_bars(&self, levels: &[f32], bars: &[f32], stdout: &mut Stdout) {
+        if let Some(snapshot) = &self.snapshot {
+            let difference: Vec<f32> = bars
+                .iter()
+                .zip(snapshot)
+                .map(|(&now, &then)| now.max(self.min_db) - then.max(self.min_db))
+                .collect();
+            self.draw_difference(&difference, stdout);
+            return;
+        }
+        if self.show_target
+            && let Some(target) = &self.target
+        {
+            self.draw_target_deviation(target, stdout);
+            return;
+        }
+        if self.draw_curve {
+            self.draw_curve_area(bars, stdout);
+            return;
+        }
+        for (band, &db) in levels.iter().enumerate() {
+            let (low_freq, high_freq) = self.band_range(band);
+            let len = self.bar_len(bars[band]);
+            let mid = (BAR_COLUMNS as f32 * theme::MID_FRACTION) as usize;
+            let high = (BAR_COLUMNS as f32 * theme::HIGH_FRACTION) as usize;
             // println!(
             //     "{:4.0} Hz - {:4.0} Hz | {:>4.1} dB | {}",
             //     low_freq, high_freq, db, bar
             // );
+            let mut label = format!(
+                "{:4.0} Hz - {:4.0} Hz | {:>4.1} dB | ",
+                low_freq, high_freq, db
+            );
+            if self.hold_secs.is_some() {
+                label.push_str(&format!("max {:>6.1} | ", self.held_by_band[band]));
+            }
+            if self.show_peaks {
+                label.push_str(&format!("{:5.0} Hz | ", self.peak_freq_by_band[band]));
+            }
+            if self.show_energy_share {
+                label.push_str(&format!("{:5.1} % | ", self.energy_share_by_band[band]));
+            }
+            execute!(
+                stdout,
+                crossterm::cursor::MoveTo(0, self.top_row + band as u16),
+                SetForegroundColor(self.theme.text),
+                Print(&label),
+            )
+            .unwrap();
+            if self.color_by_octave {
+                let centre = (low_freq * high_freq).sqrt();
+                execute!(
+                    stdout,
+                    SetForegroundColor(theme::octave_color(centre)),
+                    Print("█".repeat(len)),
+                )
+                .unwrap();
+            } else {
+                execute!(
+                    stdout,
+                    SetForegroundColor(self.theme.low),
+                    Print("█".repeat(len.min(mid))),
+                    SetForegroundColor(self.theme.mid),
+                    Print("█".repeat(len.clamp(mid, high) - mid)),
+                    SetForegroundColor(self.theme.high),
+                    Print("█".repeat(len.max(high) - high)),
+                )
+                .unwrap();
+            }
+            if len == 0 && self.floor_shading && !self.band_bins(band).is_empty() {
+                execute!(
+                    stdout,
+                    SetForegroundColor(self.theme.low),
+                    Print(FLOOR_GLYPH)
+                )
+                .unwrap();
+            }
             execute!(
                 stdout,
-                crossterm::cursor::MoveTo(0, band as u16),
-                crossterm::style::Print(format!(
-                    "{:4.0} Hz - {:4.0} Hz | {:>4.1} dB | {}",
-                    low_freq, high_freq, db, bar
-                )),
+                SetForegroundColor(self.theme.text),
+                Clear(ClearType::UntilNewLine),
             )
             .unwrap();
+
+            if let Some(overlay) = self.overlay_levels() {
+                let column = label.len() + self.bar_len(overlay[band]);
+                execute!(
+                    stdout,
+                    crossterm::cursor::MoveTo(column as u16, self.top_row + band as u16),
+                    SetForegroundColor(self.theme.peak),
+                    Print("|"),
+                    SetForegroundColor(self.theme.text),
+                )
+                .unwrap();
+            }
         }
 
         stdout.flush().unwrap();
     }
+
+    // Draws the change of every band in dB as a bar from the centre, right
+    // for a boost and left for a cut.
+    pub fn draw_difference(&self, difference: &[f32], stdout: &mut Stdout) {
+        self.draw_centred(
+            difference,
+            |db| {
+                if db < 0.0 {
+                    self.theme.low
+                } else {
+                    self.theme.high
+                }
+            },
+            stdout,
+        );
+    }
+
+    // Average level of every band minus the target, in the theme's low color
+    // within `target::TOLERANCE_DB` and its high color beyond, like the
+    // tuner's needle.
+    fn draw_target_deviation(&self, target: &[f32], stdout: &mut Stdout) {
+        let average: Vec<f32> = self
+            .power_sum_by_band
+            .iter()
+            .map(|&sum| {
+                if self.averaged_frames == 0 {
+                    self.min_db
+                } else {
+                    10.0 * (sum / self.averaged_frames as f32).log10()
+                }
+            })
+            .collect();
+        let deviation = target::deviation(&average, target, self.min_db);
+        self.draw_centred(
+            &deviation,
+            |db| {
+                if db.abs() <= target::TOLERANCE_DB {
+                    self.theme.low
+                } else {
+                    self.theme.high
+                }
+            },
+            stdout,
+        );
+    }
+
+    fn draw_centred(&self, values: &[f32], color: impl Fn(f32) -> Color, stdout: &mut Stdout) {
+        let half = BAR_COLUMNS / 2;
+        for (band, &db) in values.iter().enumerate() {
+            let (low_freq, high_freq) = self.band_range(band);
+            let label = format!(
+                "{:4.0} Hz - {:4.0} Hz | {:>+5.1} dB | ",
+                low_freq, high_freq, db
+            );
+            let len = ((db.abs() / DIFFERENCE_RANGE_DB).min(1.0) * half as f32) as usize;
+            let color = color(db);
+            let (left, right) = if db < 0.0 {
+                (" ".repeat(half - len) + &"█".repeat(len), String::new())
+            } else {
+                (" ".repeat(half), "█".repeat(len))
+            };
+            execute!(
+                stdout,
+                crossterm::cursor::MoveTo(0, self.top_row + band as u16),
+                SetForegroundColor(self.theme.text),
+                Print(&label),
+                SetForegroundColor(color),
+                Print(left),
+                SetForegroundColor(self.theme.peak),
+                Print("|"),
+                SetForegroundColor(color),
+                Print(right),
+                SetForegroundColor(self.theme.text),
+                Clear(ClearType::UntilNewLine),
+            )
+            .unwrap();
+        }
+        stdout.flush().unwrap();
+    }
+
+    // Fills the area under the band levels, eased between band centres so
+    // the top edge runs smoothly, in eighths of a row. Reference levels are
+    // marked where they lie above the curve.
+    fn draw_curve_area(&self, bars: &[f32], stdout: &mut Stdout) {
+        let columns = crossterm::terminal::size().map_or(80, |(columns, _)| columns as usize);
+        let rows = self.bands;
+        let heights = |levels: &[f32]| -> Vec<f32> {
+            (0..columns)
+                .map(|column| {
+                    // Bands are spaced evenly over log frequency, like the columns.
+                    let position = ((column as f32 + 0.5) / columns as f32 * levels.len() as f32
+                        - 0.5)
+                        .clamp(0.0, (levels.len() - 1) as f32);
+                    let below = position as usize;
+                    let above = (below + 1).min(levels.len() - 1);
+                    let t = (1.0 - ((position - below as f32) * std::f32::consts::PI).cos()) / 2.0;
+                    let db = levels[below] + (levels[above] - levels[below]) * t;
+                    self.bar_scale
+                        .fraction(db, self.min_db, self.max_db)
+                        .clamp(0.0, 1.0)
+                        * rows as f32
+                })
+                .collect()
+        };
+        let curve = heights(bars);
+        let overlay = self.overlay_levels().map(heights);
+        let (low_freq, _) = self.band_range(0);
+        let (_, high_freq) = self.band_range(self.bands - 1);
+
+        for row in 0..rows {
+            // Counted from the bottom, which is the last band's row.
+            let level = (rows - 1 - row) as f32;
+            queue!(
+                stdout,
+                crossterm::cursor::MoveTo(0, self.top_row + row as u16)
+            )
+            .unwrap();
+            let mut current = None;
+            for (column, &height) in curve.iter().enumerate() {
+                let fill = ((height - level) * 8.0).round().clamp(0.0, 8.0) as usize;
+                let marked = overlay.as_ref().is_some_and(|overlay| {
+                    overlay[column] > height && overlay[column].floor() == level
+                });
+                let (glyph, color) = if marked {
+                    ('-', self.theme.peak)
+                } else if self.color_by_octave {
+                    let freq =
+                        low_freq * (high_freq / low_freq).powf(column as f32 / columns as f32);
+                    (CURVE_GLYPHS[fill], theme::octave_color(freq))
+                } else {
+                    let fraction = (level + 0.5) / rows as f32;
+                    let color = if fraction < theme::MID_FRACTION {
+                        self.theme.low
+                    } else if fraction < theme::HIGH_FRACTION {
+                        self.theme.mid
+                    } else {
+                        self.theme.high
+                    };
+                    (CURVE_GLYPHS[fill], color)
+                };
+                // Colors are only sent where they change, to keep frames small.
+                if current != Some(color) {
+                    queue!(stdout, SetForegroundColor(color)).unwrap();
+                    current = Some(color);
+                }
+                queue!(stdout, Print(glyph)).unwrap();
+            }
+        }
+        queue!(stdout, SetForegroundColor(self.theme.text)).unwrap();
+        stdout.flush().unwrap();
+    }
+
+    fn bar_len(&self, db: f32) -> usize {
+        (self.bar_scale.fraction(db, self.min_db, self.max_db) * BAR_COLUMNS as f32).max(0.0)
+            as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_hold_drops_peaks_from_elsewhere() {
+        let mut spectrum = Spectrum::new(4, -100.0, 0.0, 0.0, 4096, 48000);
+        spectrum.update_held(&[-10.0; 4], 60.0, 2.0);
+        spectrum.update_held(&[-40.0; 4], 60.5, 2.0);
+        assert_eq!(spectrum.held_by_band, vec![-10.0; 4]);
+
+        // Seeking back to the start.
+        spectrum.reset_hold();
+        spectrum.update_held(&[-40.0; 4], 1.0, 2.0);
+        assert_eq!(spectrum.held_by_band, vec![-40.0; 4]);
+    }
 }
@@ -0,0 +1,86 @@
+use crate::source::FrameProcessor;
+
+// Creates width from a mono signal with a complementary comb pair: the delayed
+// copy is added to the left channel and subtracted from the right. The comb
+// notches of one side fall on the peaks of the other, and because the delayed
+// parts cancel in L + R, the mono sum is exactly the original signal.
+pub struct MonoToStereoProcessor {
+    pub width: f32,
+    delay_line: Vec<f32>,
+    index: usize,
+}
+
+impl MonoToStereoProcessor {
+    pub fn new(delay_ms: f32, width: f32, sample_rate: u32) -> Self {
+        let delay = ((delay_ms * 0.001 * sample_rate as f32).round() as usize).max(1);
+        MonoToStereoProcessor {
+            width,
+            delay_line: vec![0.0; delay],
+            index: 0,
+        }
+    }
+}
+
+impl FrameProcessor for MonoToStereoProcessor {
+    fn process_frame(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let dry = input[0];
+        let delayed = self.delay_line[self.index];
+        self.delay_line[self.index] = dry;
+        self.index = (self.index + 1) % self.delay_line.len();
+
+        output.push(dry + self.width * delayed);
+        output.push(dry - self.width * delayed);
+    }
+}
+
+// Turns a stereo frame into mid and side, M = (L + R) / 2 and S = (L - R) / 2,
+// so the stages that follow process them as channels 0 and 1.
+pub struct MidSideEncoder;
+
+impl FrameProcessor for MidSideEncoder {
+    fn process_frame(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        output.push((input[0] + input[1]) / 2.0);
+        output.push((input[0] - input[1]) / 2.0);
+    }
+}
+
+// Turns mid and side back into left and right, the inverse of the encoder:
+// L = M + S and R = M - S.
+pub struct MidSideDecoder;
+
+impl FrameProcessor for MidSideDecoder {
+    fn process_frame(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        output.push(input[0] + input[1]);
+        output.push(input[0] - input[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mid_side_round_trip_reproduces_left_and_right() {
+        let frames = [
+            [1.0f32, -1.0],
+            [0.5, 0.25],
+            [-0.75, 0.3],
+            [0.0, 1.0],
+            [0.123_456, -0.987_654],
+        ];
+        for frame in frames {
+            let mut mid_side = Vec::new();
+            MidSideEncoder.process_frame(&frame, &mut mid_side);
+            let mut output = Vec::new();
+            MidSideDecoder.process_frame(&mid_side, &mut output);
+            for (got, want) in output.iter().zip(frame) {
+                assert!(
+                    (got - want).abs() <= f32::EPSILON,
+                    "{:?} -> {:?}",
+                    frame,
+                    output
+                );
+            }
+        }
+    }
+}
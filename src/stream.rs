@@ -0,0 +1,106 @@
+use std::sync::mpsc::{self, Receiver};
+
+use crate::chain::ProcessorChain;
+use crate::decode::StreamDecoder;
+use crate::mic::LiveInput;
+use crate::source::{Chunk, StreamingSource};
+
+// Decoded chunks kept ahead of playback, typically one or two seconds.
+const BUFFERED_CHUNKS: usize = 64;
+
+// A file or URL played while it is decoded, so a live or endless stream
+// starts at once. It is shown on the live display like a recording: the
+// samples arrive as they are played, after the chain.
+pub struct StreamInput {
+    pub name: String,
+    pub sample_rate: u32,
+    pub channels: usize,
+    receiver: Receiver<Chunk>,
+    sink: rodio::Sink,
+    // Playback stops when the stream is dropped.
+    _stream: rodio::OutputStream,
+}
+
+impl StreamInput {
+    // Starts decoding and playing `decoder` through a chain made for its
+    // format.
+    pub fn play(
+        name: &str,
+        mut decoder: StreamDecoder,
+        chain: ProcessorChain,
+    ) -> Result<Self, String> {
+        let sample_rate = decoder.sample_rate;
+        let input_channels = decoder.channels;
+        let channels = chain.output_channels();
+
+        let (chunk_sender, chunks) = mpsc::sync_channel(BUFFERED_CHUNKS);
+        std::thread::spawn(move || {
+            loop {
+                let chunk = match decoder.next_chunk() {
+                    Ok(Some(chunk)) => Ok(chunk),
+                    Ok(None) => break,
+                    Err(message) => Err(message),
+                };
+                let failed = chunk.is_err();
+                // Playback is only gone while shutting down.
+                if chunk_sender.send(chunk).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let stream = rodio::OutputStreamBuilder::open_default_stream()
+            .map_err(|e| format!("Failed to open the audio output: {}", e))?;
+        let sink = rodio::Sink::connect_new(stream.mixer());
+        let (sender, receiver) = mpsc::channel();
+        sink.append(StreamingSource::new(
+            chunks,
+            sender,
+            input_channels,
+            sample_rate,
+            chain,
+        ));
+        Ok(StreamInput {
+            name: name.to_string(),
+            sample_rate,
+            channels,
+            receiver,
+            sink,
+            _stream: stream,
+        })
+    }
+}
+
+impl LiveInput for StreamInput {
+    fn label(&self) -> String {
+        format!("Streaming {}", self.name)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn receive(&self) -> Result<Vec<f32>, String> {
+        let mut samples = Vec::new();
+        for chunk in self.receiver.try_iter() {
+            samples.extend(chunk?);
+        }
+        Ok(samples)
+    }
+
+    fn finished(&self) -> bool {
+        self.sink.empty()
+    }
+
+    fn set_paused(&self, paused: bool) {
+        if paused {
+            self.sink.pause();
+        } else {
+            self.sink.play();
+        }
+    }
+}
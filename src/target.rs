@@ -0,0 +1,87 @@
+// Deviations within this many dB of the target count as on target.
+pub const TOLERANCE_DB: f32 = 3.0;
+
+// Tonal target, e.g. a house curve, as frequency and level breakpoints.
+// Levels in between are interpolated linearly over log frequency and held
+// flat beyond the first and last breakpoint.
+pub struct TargetCurve {
+    // Sorted by frequency.
+    points: Vec<(f32, f32)>,
+}
+
+impl TargetCurve {
+    // Reads one `<hz> <db>` breakpoint per line, separated by whitespace or
+    // a comma; `#` starts a comment.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read target curve {}: {}", path, e))?;
+        let mut points = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let values: Vec<&str> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|v| !v.is_empty())
+                .collect();
+            if values.is_empty() {
+                continue;
+            }
+            let invalid = || {
+                format!(
+                    "Invalid target curve point in {} on line {}: expected <hz> <db>",
+                    path,
+                    number + 1
+                )
+            };
+            let [freq, db] = values[..] else {
+                return Err(invalid());
+            };
+            let freq: f32 = freq.parse().map_err(|_| invalid())?;
+            let db: f32 = db.parse().map_err(|_| invalid())?;
+            if !(freq > 0.0 && freq.is_finite() && db.is_finite()) {
+                return Err(invalid());
+            }
+            points.push((freq, db));
+        }
+        if points.is_empty() {
+            return Err(format!("{} has no target curve points", path));
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(TargetCurve { points })
+    }
+
+    pub fn level_at(&self, freq: f32) -> f32 {
+        let after = self.points.partition_point(|&(f, _)| f < freq);
+        if after == 0 {
+            return self.points[0].1;
+        }
+        if after == self.points.len() {
+            return self.points[after - 1].1;
+        }
+        let (low_freq, low_db) = self.points[after - 1];
+        let (high_freq, high_db) = self.points[after];
+        let t = (freq / low_freq).ln() / (high_freq / low_freq).ln();
+        low_db + (high_db - low_db) * t
+    }
+}
+
+// Deviation of `levels` from `target` per band, with the average offset
+// removed, so only the tonal balance counts and not the overall loudness.
+// Bands at or below `min_db` have no content and are left out of the offset.
+pub fn deviation(levels: &[f32], target: &[f32], min_db: f32) -> Vec<f32> {
+    let audible: Vec<f32> = levels
+        .iter()
+        .zip(target)
+        .filter(|(db, _)| **db > min_db)
+        .map(|(db, target)| db - target)
+        .collect();
+    let offset = if audible.is_empty() {
+        0.0
+    } else {
+        audible.iter().sum::<f32>() / audible.len() as f32
+    };
+    levels
+        .iter()
+        .zip(target)
+        .map(|(db, target)| db.max(min_db) - target - offset)
+        .collect()
+}
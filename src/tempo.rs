@@ -0,0 +1,104 @@
+use crate::analysis;
+use crate::onset::OnsetDetector;
+use crate::spectrum::Spectrum;
+
+// Hop between the frames of the estimate, which places onsets to about
+// 12 ms at 44.1 kHz.
+const HOP_SIZE: usize = 512;
+// Tempo range searched. Wider ranges make halving and doubling errors likelier.
+const MIN_BPM: f32 = 70.0;
+const MAX_BPM: f32 = 180.0;
+// Below this autocorrelation of the flux at the beat period, relative to its
+// energy, onsets are too irregular for a grid.
+const MIN_PERIODICITY: f32 = 0.25;
+
+// Beats at a constant tempo, the first at `offset_secs`; frames are placed
+// on it by the timestamp of the end of their window, like in the display.
+#[derive(Clone, Copy, Debug)]
+pub struct BeatGrid {
+    pub period_secs: f32,
+    pub offset_secs: f32,
+}
+
+impl BeatGrid {
+    pub fn bpm(&self) -> f32 {
+        60.0 / self.period_secs
+    }
+
+    // Index of the beat `timestamp` falls in, negative before the first.
+    pub fn beat_at(&self, timestamp: f32) -> i64 {
+        ((timestamp - self.offset_secs) / self.period_secs).floor() as i64
+    }
+}
+
+// Estimates a constant tempo from interleaved samples: the spectral flux of
+// the onset detector is autocorrelated over the tempo range and the grid is
+// shifted to the phase with the most flux on its beats. `None` if the
+// material has no steady pulse. Tempo changes aren't followed.
+pub fn estimate_beats(spectrum: &Spectrum, samples: &[f32]) -> Option<BeatGrid> {
+    let frame_secs = HOP_SIZE as f32 / spectrum.sample_rate as f32;
+    let mut onsets = OnsetDetector::new(16);
+    let mut flux: Vec<f32> = analysis::band_matrix(spectrum, samples, HOP_SIZE)
+        .iter()
+        .map(|levels| onsets.process(levels, spectrum.min_db).0)
+        .collect();
+    let mean = flux.iter().sum::<f32>() / flux.len().max(1) as f32;
+    for value in flux.iter_mut() {
+        *value -= mean;
+    }
+
+    let min_lag = (60.0 / MAX_BPM / frame_secs).floor() as usize;
+    let max_lag = (60.0 / MIN_BPM / frame_secs).ceil() as usize;
+    // A few beats are needed to tell a pulse from chance.
+    if flux.len() < 4 * max_lag {
+        return None;
+    }
+    let autocorrelation = |lag: usize| -> f32 {
+        flux.iter()
+            .zip(&flux[lag..])
+            .map(|(a, b)| a * b)
+            .sum::<f32>()
+            / (flux.len() - lag) as f32
+    };
+    let energy = autocorrelation(0);
+    let (lag, peak) = (min_lag..=max_lag)
+        .map(|lag| (lag, autocorrelation(lag)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if energy <= 0.0 || peak / energy < MIN_PERIODICITY {
+        return None;
+    }
+    // The period is measured over several beats where the track is long
+    // enough, which divides the error of rounding to whole frames. The peak
+    // also lies between frames; a parabola through its neighbours finds it.
+    let beats = (flux.len() / lag / 4).clamp(1, 4);
+    let (long_lag, long_peak) = (beats * lag - beats..=beats * lag + beats)
+        .map(|lag| (lag, autocorrelation(lag)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let before = autocorrelation(long_lag - 1);
+    let after = autocorrelation(long_lag + 1);
+    let curvature = before - 2.0 * long_peak + after;
+    let shift = if curvature < 0.0 {
+        (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let period = (long_lag as f32 + shift) / beats as f32;
+
+    let phase = (0..lag)
+        .map(|start| {
+            let on_beats: f32 = (0..)
+                .map(|beat| (start as f32 + beat as f32 * period).round() as usize)
+                .take_while(|&frame| frame < flux.len())
+                .map(|frame| flux[frame])
+                .sum();
+            (start, on_beats)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(start, _)| start);
+    // Flux frame `i` is timestamped at the end of its window.
+    let window_secs = spectrum.fft_size as f32 / spectrum.sample_rate as f32;
+    Some(BeatGrid {
+        period_secs: period * frame_secs,
+        offset_secs: phase as f32 * frame_secs + window_secs,
+    })
+}
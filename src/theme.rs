@@ -0,0 +1,168 @@
+use crossterm::style::Color;
+
+pub const BUILTIN_THEMES: &str = "default, meter, contrast";
+
+// Colors of every rendered element. Bars are drawn in the low color up to
+// half their full length, mid up to `HIGH_FRACTION` and high beyond.
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub background: Color,
+    pub low: Color,
+    pub mid: Color,
+    pub high: Color,
+    // Reference overlay markers.
+    pub peak: Color,
+    pub text: Color,
+}
+
+pub const MID_FRACTION: f32 = 0.5;
+pub const HIGH_FRACTION: f32 = 0.8;
+
+// C0, where octave 0 starts when bars are colored by octave.
+const OCTAVE_BASE_HZ: f32 = 16.352;
+// Distinct hues before the octave colors repeat. The eleven octaves up to
+// 20 kHz never wrap, so the lowest and highest don't share a color.
+const OCTAVE_HUES: usize = 12;
+
+impl Default for Theme {
+    // The terminal's own colors, as before themes existed.
+    fn default() -> Self {
+        Theme {
+            background: Color::Reset,
+            low: Color::Reset,
+            mid: Color::Reset,
+            high: Color::Red,
+            peak: Color::DarkGrey,
+            text: Color::Reset,
+        }
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Theme::default()),
+            "meter" => Some(Theme {
+                background: Color::Reset,
+                low: Color::Green,
+                mid: Color::Yellow,
+                high: Color::Red,
+                peak: Color::Cyan,
+                text: Color::Reset,
+            }),
+            // Bright colors on black, which stay apart for most kinds of
+            // color blindness.
+            "contrast" => Some(Theme {
+                background: Color::Black,
+                low: Color::White,
+                mid: Color::Cyan,
+                high: Color::Yellow,
+                peak: Color::Magenta,
+                text: Color::White,
+            }),
+            _ => None,
+        }
+    }
+
+    // Takes a built-in theme name or the path of a theme file.
+    pub fn load(name_or_path: &str) -> Result<Self, String> {
+        if let Some(theme) = Theme::builtin(name_or_path) {
+            return Ok(theme);
+        }
+        let text = std::fs::read_to_string(name_or_path)
+            .map_err(|e| format!("Failed to read theme {}: {}", name_or_path, e))?;
+        Theme::parse(&text).map_err(|e| format!("Invalid theme {}: {}", name_or_path, e))
+    }
+
+    // Parses `key = "color"` and `#` comment lines, a subset of TOML. Colors
+    // are crossterm names like `dark_grey`, `reset` or `#rrggbb`; missing keys
+    // keep the default theme's color.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut theme = Theme::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected key = value", number + 1))?;
+            let value = value.trim().trim_matches('"');
+            let color = parse_color(value)
+                .ok_or_else(|| format!("line {}: unknown color {}", number + 1, value))?;
+            match key.trim() {
+                "background" => theme.background = color,
+                "low" => theme.low = color,
+                "mid" => theme.mid = color,
+                "high" => theme.high = color,
+                "peak" => theme.peak = color,
+                "text" => theme.text = color,
+                other => return Err(format!("line {}: unknown key {}", number + 1, other)),
+            }
+        }
+        Ok(theme)
+    }
+}
+
+// Fully saturated color of a hue from 0 (red) through green and blue back
+// to red at 1.
+pub fn hue_color(hue: f32) -> Color {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let rising = ((sector % 1.0) * 255.0) as u8;
+    let falling = 255 - rising;
+    let (r, g, b) = match sector as usize {
+        0 => (255, rising, 0),
+        1 => (falling, 255, 0),
+        2 => (0, 255, rising),
+        3 => (0, falling, 255),
+        4 => (rising, 0, 255),
+        _ => (255, 0, falling),
+    };
+    Color::Rgb { r, g, b }
+}
+
+// Color of the octave, counted in C to C steps, that `frequency` is in.
+pub fn octave_color(frequency: f32) -> Color {
+    let octave = (frequency / OCTAVE_BASE_HZ).log2().floor().max(0.0) as usize;
+    hue_color((octave % OCTAVE_HUES) as f32 / OCTAVE_HUES as f32)
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    if value == "reset" {
+        return Some(Color::Reset);
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        // Byte slicing below would panic inside a multibyte character.
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    Color::try_from(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(
+            parse_color("#ff8000"),
+            Some(Color::Rgb {
+                r: 255,
+                g: 128,
+                b: 0
+            })
+        );
+        assert_eq!(parse_color("#ff80"), None);
+        assert_eq!(parse_color("#+f8000"), None);
+        // Six bytes, but not six characters.
+        assert_eq!(parse_color("#ééé"), None);
+    }
+}
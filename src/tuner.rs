@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+use std::io::{Stdout, Write};
+
+use crossterm::cursor::MoveTo;
+use crossterm::execute;
+use crossterm::style::{Print, SetForegroundColor};
+
+use crate::pitch;
+use crate::plot;
+use crate::theme::Theme;
+
+// Readings kept to judge whether the pitch is steady.
+const STABILITY_FRAMES: usize = 8;
+// Largest spread of those readings, in cents, that still counts as stable.
+const STABLE_SPREAD: f32 = 6.0;
+// Deviation shown as in tune.
+const IN_TUNE_CENTS: f32 = 5.0;
+// The needle scale spans -50 to +50 cents, one column per cent.
+const SCALE_CENTS: usize = 100;
+
+// Note, frequency and cents deviation of the strongest pitch, smoothed so the
+// needle doesn't jitter. Smoothing restarts whenever the note changes.
+pub struct Tuner {
+    pub a4: f32,
+    pub smoothing: f32,
+    note: Option<i32>,
+    frequency: f32,
+    cents: f32,
+    recent: VecDeque<f32>,
+}
+
+impl Tuner {
+    pub fn new(a4: f32, smoothing: f32) -> Self {
+        Tuner {
+            a4,
+            smoothing,
+            note: None,
+            frequency: 0.0,
+            cents: 0.0,
+            recent: VecDeque::with_capacity(STABILITY_FRAMES),
+        }
+    }
+
+    // Feeds the pitch of one frame, `None` while silent.
+    pub fn update(&mut self, frequency: Option<f32>) {
+        let Some(frequency) = frequency else {
+            self.note = None;
+            self.recent.clear();
+            return;
+        };
+        let (note, cents) = pitch::frequency_to_note(frequency, self.a4);
+        if self.note == Some(note) {
+            self.cents = self.smoothing * self.cents + (1.0 - self.smoothing) * cents;
+            self.frequency = self.smoothing * self.frequency + (1.0 - self.smoothing) * frequency;
+        } else {
+            self.note = Some(note);
+            self.cents = cents;
+            self.frequency = frequency;
+            self.recent.clear();
+        }
+        if self.recent.len() == STABILITY_FRAMES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(cents);
+    }
+
+    fn stable(&self) -> bool {
+        let (min, max) = self
+            .recent
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), &c| {
+                (min.min(c), max.max(c))
+            });
+        self.recent.len() == STABILITY_FRAMES && max - min <= STABLE_SPREAD
+    }
+
+    // Draws the note in large letters, the frequency, a needle from -50 to
+    // +50 cents and whether the reading is stable, starting at row `top`.
+    pub fn draw(&self, top: u16, theme: &Theme, stdout: &mut Stdout) {
+        let name = self.note.map_or("--".to_string(), pitch::note_name);
+        let color = match self.note {
+            None => theme.text,
+            Some(_) if self.cents.abs() < IN_TUNE_CENTS => theme.low,
+            Some(_) if self.cents.abs() < 3.0 * IN_TUNE_CENTS => theme.mid,
+            Some(_) => theme.high,
+        };
+
+        // Every glyph pixel is two block characters wide, about square.
+        for row in 0..plot::GLYPH_HEIGHT {
+            let mut line = String::new();
+            for c in name.chars() {
+                let bits = plot::glyph(c)[row];
+                for column in 0..plot::GLYPH_WIDTH {
+                    let lit = bits >> (plot::GLYPH_WIDTH - 1 - column) & 1 != 0;
+                    line.push_str(if lit { "██" } else { "  " });
+                }
+                line.push_str("  ");
+            }
+            execute!(
+                stdout,
+                MoveTo(2, top + row as u16),
+                SetForegroundColor(color),
+                Print(line)
+            )
+            .unwrap();
+        }
+
+        let reading = match self.note {
+            Some(_) => format!(
+                "{:.1} Hz  {:+.1} cents  (A4 = {} Hz)",
+                self.frequency, self.cents, self.a4
+            ),
+            None => format!("listening...  (A4 = {} Hz)", self.a4),
+        };
+        let scale: String = (0..=SCALE_CENTS)
+            .map(|column| match column % 10 {
+                _ if column == SCALE_CENTS / 2 => '┼',
+                0 => '┬',
+                _ => '─',
+            })
+            .collect();
+        // Labels centred under the ends and the middle of the scale.
+        let labels = format!(
+            "-50{:>middle$}{:>end$}",
+            "0",
+            "+50",
+            middle = SCALE_CENTS / 2 - 1,
+            end = SCALE_CENTS / 2 + 1
+        );
+        execute!(
+            stdout,
+            SetForegroundColor(theme.text),
+            MoveTo(2, top + 6),
+            Print(reading),
+            MoveTo(2, top + 8),
+            Print(scale),
+            MoveTo(1, top + 10),
+            Print(labels),
+        )
+        .unwrap();
+
+        if self.note.is_some() {
+            let offset = self.cents.clamp(-50.0, 50.0) + SCALE_CENTS as f32 / 2.0;
+            let (status, status_color) = match self.stable() {
+                true if self.cents.abs() < IN_TUNE_CENTS => ("● in tune", theme.low),
+                true => ("● stable", theme.mid),
+                false => ("○ settling", theme.text),
+            };
+            execute!(
+                stdout,
+                SetForegroundColor(color),
+                MoveTo(2 + offset.round() as u16, top + 9),
+                Print("▲"),
+                SetForegroundColor(status_color),
+                MoveTo(2, top + 12),
+                Print(status),
+                SetForegroundColor(theme.text),
+            )
+            .unwrap();
+        }
+
+        stdout.flush().unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use std::f64::consts::PI;
+
+use crate::biquad::Biquad;
+use crate::loudness;
+
+// Frequency weighting applied before peak detection, see `--peak-weighting`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weighting {
+    Flat,
+    // IEC 61672 A-weighting, following the ear's sensitivity at low levels.
+    A,
+    // The BS.1770 K filter that loudness is measured with.
+    K,
+}
+
+impl Weighting {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "flat" => Some(Weighting::Flat),
+            "a" => Some(Weighting::A),
+            "k" => Some(Weighting::K),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Weighting::Flat => "flat",
+            Weighting::A => "A",
+            Weighting::K => "K",
+        }
+    }
+}
+
+// Pole frequencies of the analog A-weighting filter.
+const A_POLES_HZ: [f64; 4] = [20.598997, 107.65265, 737.86223, 12194.217];
+
+// The weighting filter of one channel, keeping its state between calls.
+pub struct WeightingFilter {
+    sections: Vec<Biquad>,
+    gain: f64,
+}
+
+impl WeightingFilter {
+    pub fn new(weighting: Weighting, sample_rate: u32) -> Self {
+        let sections = match weighting {
+            Weighting::Flat => Vec::new(),
+            Weighting::A => a_sections(sample_rate),
+            Weighting::K => {
+                let (shelf, high_pass) = loudness::k_filter(sample_rate);
+                vec![shelf, high_pass]
+            }
+        };
+        // A-weighting is defined as 0 dB at 1 kHz; the K filter has no such
+        // normalization and is left as BS.1770 specifies it.
+        let gain = if weighting == Weighting::A {
+            1.0 / sections
+                .iter()
+                .map(|section| section.magnitude_at(1000.0, sample_rate))
+                .product::<f64>()
+        } else {
+            1.0
+        };
+        WeightingFilter { sections, gain }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self
+            .sections
+            .iter_mut()
+            .fold(input, |sample, section| section.process(sample));
+        (output as f64 * self.gain) as f32
+    }
+}
+
+// The analog A-weighting filter, zeros at DC and the poles above, as three
+// second-order sections by the bilinear transform. Every pole is prewarped
+// so it lands on its analog frequency, and held just below Nyquist where it
+// would lie beyond. The transform still pulls the response down towards
+// Nyquist: at 44.1 and 48 kHz it reads about 1 dB low at 10 kHz and 4 dB
+// low at 16 kHz, inside the IEC 61672 class 1 tolerances.
+fn a_sections(sample_rate: u32) -> Vec<Biquad> {
+    let c = 2.0 * sample_rate as f64;
+    let [w1, w2, w3, w4] = A_POLES_HZ.map(|freq| {
+        let freq = freq.min(0.45 * sample_rate as f64);
+        c * (PI * freq / sample_rate as f64).tan()
+    });
+    let bilinear = |b: [f64; 3], a: [f64; 3]| {
+        let digital = |p: [f64; 3]| {
+            [
+                p[0] * c * c + p[1] * c + p[2],
+                2.0 * (p[2] - p[0] * c * c),
+                p[0] * c * c - p[1] * c + p[2],
+            ]
+        };
+        Biquad::normalized(digital(b), digital(a))
+    };
+    vec![
+        bilinear([1.0, 0.0, 0.0], [1.0, 2.0 * w1, w1 * w1]),
+        bilinear([1.0, 0.0, 0.0], [1.0, w2 + w3, w2 * w3]),
+        bilinear([0.0, 0.0, w4 * w4], [1.0, 2.0 * w4, w4 * w4]),
+    ]
+}
+
+// Peak detection behind the weighting filter of every channel. The filters
+// keep their state, so a signal can be measured in pieces as it plays.
+pub struct WeightedPeak {
+    pub weighting: Weighting,
+    filters: Vec<WeightingFilter>,
+}
+
+impl WeightedPeak {
+    pub fn new(weighting: Weighting, channels: usize, sample_rate: u32) -> Self {
+        WeightedPeak {
+            weighting,
+            filters: (0..channels)
+                .map(|_| WeightingFilter::new(weighting, sample_rate))
+                .collect(),
+        }
+    }
+
+    // Highest weighted sample of interleaved samples, over all channels.
+    pub fn peak(&mut self, samples: &[f32]) -> f32 {
+        let mut max = 0.0f32;
+        for frame in samples.chunks_exact(self.filters.len()) {
+            for (&s, filter) in frame.iter().zip(self.filters.iter_mut()) {
+                max = max.max(filter.process(s).abs());
+            }
+        }
+        max
+    }
+}
@@ -0,0 +1,127 @@
+use std::f32::consts::PI;
+
+// Names accepted by `from_name`, for help and error messages.
+pub const WINDOW_NAMES: &str = "hann, hamming, blackman or rectangular";
+
+// Window applied to every analysis frame before the FFT. Blackman trades a
+// wider main lobe for much lower leakage far from a tone; rectangular, i.e.
+// no window, resolves tones closest together but leaks the most.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Window {
+    Hann,
+    Hamming,
+    Blackman,
+    Rectangular,
+}
+
+impl Window {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hann" => Some(Window::Hann),
+            "hamming" => Some(Window::Hamming),
+            "blackman" => Some(Window::Blackman),
+            "rectangular" => Some(Window::Rectangular),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Window::Hann => "hann",
+            Window::Hamming => "hamming",
+            Window::Blackman => "blackman",
+            Window::Rectangular => "rectangular",
+        }
+    }
+
+    // Coefficient `i` of a symmetric window of `len` points.
+    pub fn coefficient(&self, i: usize, len: usize) -> f32 {
+        let phase = 2.0 * PI * i as f32 / (len as f32 - 1.0);
+        match self {
+            Window::Hann => 0.5 * (1.0 - phase.cos()),
+            Window::Hamming => 0.54 - 0.46 * phase.cos(),
+            Window::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+            Window::Rectangular => 1.0,
+        }
+    }
+
+    // Mean coefficient, the window's amplitude gain for a tone in a bin.
+    pub fn coherent_gain(&self) -> f32 {
+        match self {
+            Window::Hann => 0.5,
+            Window::Hamming => 0.54,
+            Window::Blackman => 0.42,
+            Window::Rectangular => 1.0,
+        }
+    }
+
+    // Equivalent noise bandwidth in bins: how much wider than one bin the
+    // window's response is for broadband noise. With levels scaled so tones
+    // read correctly, noise reads this much too high.
+    pub fn enbw(&self) -> f32 {
+        match self {
+            Window::Hann => 1.5,
+            Window::Hamming => 1.3628,
+            Window::Blackman => 1.7268,
+            Window::Rectangular => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::{FftPlanner, num_complex::Complex};
+
+    // Noise bandwidth of the window measured on white noise: the mean power
+    // per bin against what a rectangular window passes, relative to the
+    // window's gain for a tone.
+    fn measured_enbw(window: Window) -> f32 {
+        const LEN: usize = 1024;
+        const FRAMES: usize = 400;
+        let fft = FftPlanner::new().plan_fft_forward(LEN);
+        let mut rng = 0x2545_F491u32;
+        let mut noise = || {
+            rng ^= rng << 13;
+            rng ^= rng >> 17;
+            rng ^= rng << 5;
+            rng as f32 / u32::MAX as f32 - 0.5
+        };
+        let mut power = 0.0f64;
+        let mut variance = 0.0f64;
+        for _ in 0..FRAMES {
+            let mut buffer: Vec<Complex<f32>> = (0..LEN)
+                .map(|i| {
+                    let s = noise();
+                    variance += (s * s) as f64;
+                    Complex::new(s * window.coefficient(i, LEN), 0.0)
+                })
+                .collect();
+            fft.process(&mut buffer);
+            power += buffer.iter().map(|c| c.norm_sqr() as f64).sum::<f64>();
+        }
+        // A rectangular window passes the noise variance times LEN per bin.
+        let rectangular = variance / (FRAMES * LEN) as f64 * LEN as f64;
+        let per_bin = power / (FRAMES * LEN) as f64;
+        (per_bin / rectangular) as f32 / window.coherent_gain().powi(2)
+    }
+
+    #[test]
+    fn enbw_matches_white_noise() {
+        for window in [
+            Window::Hann,
+            Window::Hamming,
+            Window::Blackman,
+            Window::Rectangular,
+        ] {
+            let measured = measured_enbw(window);
+            assert!(
+                (measured - window.enbw()).abs() < 0.02 * window.enbw(),
+                "{}: measured {} against {}",
+                window.name(),
+                measured,
+                window.enbw()
+            );
+        }
+    }
+}